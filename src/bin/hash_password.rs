@@ -0,0 +1,23 @@
+use std::io::{self, BufRead, Write};
+
+use robot_admin::auth::hash_password;
+
+// 生成用户文件中 password_hash 字段的值
+// 用法：hash_password [password]，未指定时从标准输入读取
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let password = match std::env::args().nth(1) {
+        Some(password) => password,
+        None => {
+            eprint!("Password: ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        return Err("Password must not be empty".into());
+    }
+    println!("{}", hash_password(&password)?);
+    Ok(())
+}
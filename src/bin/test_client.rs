@@ -1,34 +1,93 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio::time;
-use tonic::{transport::Channel, Request, Status};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tonic::{Request, Status};
 use chrono::Utc;
 
 use robot_admin::grpc::game_control::game_control_client::GameControlClient;
+use robot_admin::grpc::game_control::{client_message, server_message};
 use robot_admin::grpc::game_control::{
-    RegisterRequest, StatusRequest, StatusUpdate, CurrentCommand,
+    RegisterRequest, RegisterResponse, StatusRequest, StatusUpdate, CurrentCommand,
+    ClientMessage, Heartbeat, CommandResult, CommandCapability, ParameterCapability,
+    CommandAck, CommandStarted, TimeSyncRequest,
 };
 
+// 测试客户端支持的最大玩家数
+const MAX_PLAYERS: u32 = 1000;
+// 测试客户端实现的协议版本
+const PROTOCOL_VERSION: u32 = 1;
+// 时间同步间隔（秒）
+const TIME_SYNC_INTERVAL_SECS: u64 = 10;
+
 // 全局状态
 #[derive(Clone)]
 struct ClientState {
+    // 服务器地址和 TLS 设置，重新连接时使用
+    endpoint: Endpoint,
     client_id: Option<String>,
+    // 客户端实例的持久标识，重新注册时用于恢复身份
+    instance_key: String,
+    // 注册时上报的标签
+    labels: HashMap<String, String>,
+    // 注册令牌，服务器启用客户端认证时需要
+    enrollment_token: String,
+    // 注册时服务器发放的会话密钥，之后的一元请求在元数据中携带
+    session_secret: Option<String>,
     current_command: Option<CurrentCommand>,
+    // 已确认、等待统一开始时间的屏障命令
+    waiting_command: Option<CurrentCommand>,
     last_update: i64,
     reconnect_count: u32,
     command_start_time: Option<i64>,
+    // 最近一次时间同步得到的时钟偏差和往返时间（微秒）
+    clock_offset_us: Option<i64>,
+    rtt_us: Option<i64>,
 }
 
 impl ClientState {
-    fn new() -> Self {
+    fn new(endpoint: Endpoint, instance_key: String, labels: HashMap<String, String>, enrollment_token: String) -> Self {
         Self {
+            endpoint,
             client_id: None,
+            instance_key,
+            labels,
+            enrollment_token,
+            session_secret: None,
             current_command: None,
+            waiting_command: None,
             last_update: 0,
             reconnect_count: 0,
             command_start_time: None,
+            clock_offset_us: None,
+            rtt_us: None,
+        }
+    }
+
+    // 记录注册结果，服务器未启用客户端认证时会话密钥为空
+    fn registered(&mut self, response: &RegisterResponse) {
+        self.client_id = Some(response.client_id.clone());
+        self.session_secret = Some(response.session_secret.clone()).filter(|secret| !secret.is_empty());
+    }
+
+    // 构造携带客户端ID和会话密钥的请求
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let (Some(client_id), Some(secret)) = (&self.client_id, &self.session_secret) {
+            if let (Ok(client_id), Ok(secret)) = (client_id.parse(), secret.parse()) {
+                request.metadata_mut().insert("x-client-id", client_id);
+                request.metadata_mut().insert("x-client-secret", secret);
+            }
         }
+        request
+    }
+
+    // 按时钟偏差换算的服务器当前时间（毫秒）
+    fn server_now_ms(&self) -> i64 {
+        (Utc::now().timestamp_micros() + self.clock_offset_us.unwrap_or(0)) / 1000
     }
 
     fn start_command(&mut self, command: CurrentCommand) {
@@ -47,6 +106,73 @@ impl ClientState {
         }
     }
 
+    // 开始执行等待中的屏障命令
+    fn start_waiting(&mut self, command_id: &str) -> bool {
+        match self.waiting_command.take() {
+            Some(cmd) if cmd.command_id == command_id => {
+                self.start_command(cmd);
+                true
+            }
+            other => {
+                self.waiting_command = other;
+                false
+            }
+        }
+    }
+
+    // 中止指定的当前命令，并生成确认取消的执行结果
+    fn abort_command(&mut self, command_id: &str) -> Option<CommandResult> {
+        // 尚未开始的屏障命令直接丢弃
+        let started_at = if self.waiting_command.as_ref().is_some_and(|cmd| cmd.command_id == command_id) {
+            self.waiting_command = None;
+            Utc::now().timestamp()
+        } else {
+            if self.current_command.as_ref()?.command_id != command_id {
+                return None;
+            }
+            self.current_command = None;
+            self.command_start_time.take().unwrap_or_default()
+        };
+
+        Some(CommandResult {
+            command_id: command_id.to_string(),
+            success: false,
+            message: "Command aborted".to_string(),
+            client_id: self.client_id.clone().unwrap_or_default(),
+            exit_code: 130,
+            error: String::new(),
+            output: HashMap::new(),
+            started_at,
+            finished_at: Utc::now().timestamp(),
+            cancelled: true,
+        })
+    }
+
+    // 结束当前命令并生成执行结果，参数 fail=true 时模拟执行失败
+    fn finish_command(&mut self) -> Option<CommandResult> {
+        let started_at = self.command_start_time?;
+        let cmd = self.current_command.take()?;
+        self.command_start_time = None;
+
+        let finished_at = Utc::now().timestamp();
+        let failed = cmd.parameters.get("fail").is_some_and(|value| value == "true");
+        let mut output = HashMap::new();
+        output.insert("duration_secs".to_string(), (finished_at - started_at).to_string());
+
+        Some(CommandResult {
+            command_id: cmd.command_id,
+            success: !failed,
+            message: if failed { "Command failed" } else { "Command finished" }.to_string(),
+            client_id: self.client_id.clone().unwrap_or_default(),
+            exit_code: if failed { 1 } else { 0 },
+            error: if failed { "Simulated failure".to_string() } else { String::new() },
+            output,
+            started_at,
+            finished_at,
+            cancelled: false,
+        })
+    }
+
     fn is_command_finished(&self) -> bool {
         if let Some(start_time) = self.command_start_time {
             // 假设每个命令执行5秒
@@ -57,13 +183,16 @@ impl ClientState {
     }
 }
 
-async fn connect_with_retry() -> Result<GameControlClient<Channel>, Box<dyn std::error::Error + Send + Sync>> {
+async fn connect_with_retry(
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<GameControlClient<Channel>, Box<dyn std::error::Error + Send + Sync>> {
+    let endpoint = state.lock().unwrap().endpoint.clone();
     let mut retry_count = 0;
     let max_retries = 10; // 最多重试10次
     let mut delay = Duration::from_secs(1);
 
     loop {
-        match GameControlClient::connect("http://127.0.0.1:50051").await {
+        match endpoint.connect().await.map(GameControlClient::new) {
             Ok(client) => {
                 println!("Successfully connected to server");
                 return Ok(client);
@@ -83,19 +212,88 @@ async fn connect_with_retry() -> Result<GameControlClient<Channel>, Box<dyn std:
     }
 }
 
-async fn register_client(client: &mut GameControlClient<Channel>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let request = Request::new(RegisterRequest {
-        client_name: "Test Client".to_string(),
-        client_type: "Test".to_string(),
-        version: "1.0.0".to_string(),
-        max_players: 1000,
-    });
+fn parameter(name: &str, kind: &str, min: Option<f64>, max: Option<f64>) -> ParameterCapability {
+    ParameterCapability {
+        name: name.to_string(),
+        r#type: kind.to_string(),
+        min,
+        max,
+        ..Default::default()
+    }
+}
 
+// 测试客户端支持的命令，注册时上报给服务器
+// 每个命令都支持 fail=true 参数来模拟执行失败
+fn supported_commands() -> Vec<CommandCapability> {
+    let fail = parameter("fail", "boolean", None, None);
+    vec![
+        CommandCapability {
+            name: "login".to_string(),
+            description: "Log in a range of players".to_string(),
+            parameters: vec![
+                parameter("player_count", "integer", Some(1.0), Some(f64::from(MAX_PLAYERS))),
+                parameter("player_offset", "integer", Some(0.0), None),
+                fail.clone(),
+            ],
+        },
+        CommandCapability {
+            name: "logout".to_string(),
+            description: "Log out all players".to_string(),
+            parameters: vec![fail.clone()],
+        },
+        CommandCapability {
+            name: "move".to_string(),
+            description: "Walk the logged-in players around".to_string(),
+            parameters: vec![
+                parameter("speed", "float", Some(0.1), Some(10.0)),
+                parameter("map_name", "string", None, None),
+                fail,
+            ],
+        },
+    ]
+}
+
+// 构造注册请求，携带之前的客户端ID和实例标识以便服务器恢复原有身份
+fn register_request(state: &ClientState) -> RegisterRequest {
     println!("\n[Sending Register Request] ----------------------------------------");
     println!("Name: Test Client");
     println!("Type: Test");
     println!("Version: 1.0.0");
-    println!("Max Players: 1000");
+    println!("Max Players: {}", MAX_PLAYERS);
+    println!("Protocol Version: {}", PROTOCOL_VERSION);
+    if let Some(client_id) = &state.client_id {
+        println!("Previous Client ID: {}", client_id);
+    }
+    if !state.instance_key.is_empty() {
+        println!("Instance Key: {}", state.instance_key);
+    }
+    for (key, value) in &state.labels {
+        println!("Label {}: {}", key, value);
+    }
+
+    RegisterRequest {
+        client_name: "Test Client".to_string(),
+        client_type: "Test".to_string(),
+        version: "1.0.0".to_string(),
+        max_players: MAX_PLAYERS,
+        client_id: state.client_id.clone().unwrap_or_default(),
+        instance_key: state.instance_key.clone(),
+        labels: state.labels.clone(),
+        protocol_version: PROTOCOL_VERSION,
+        commands: supported_commands(),
+        enrollment_token: state.enrollment_token.clone(),
+    }
+}
+
+async fn register_client(
+    client: &mut GameControlClient<Channel>,
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    // 携带之前的会话密钥，服务器启用客户端认证时据此恢复原有身份
+    let request = {
+        let state = state.lock().unwrap();
+        state.request(register_request(&state))
+    };
 
     let response = client.register(request).await?;
     let response = response.into_inner();
@@ -103,28 +301,27 @@ async fn register_client(client: &mut GameControlClient<Channel>) -> Result<Stri
     println!("\n[Register Response] ----------------------------------------");
     println!("Success: {}", response.success);
     println!("Client ID: {}", response.client_id);
+    println!("Resumed: {}", response.resumed);
+    println!("Server Protocol Version: {}", response.protocol_version);
     println!("Message: {}", response.message);
-    
+
+    state.lock().unwrap().registered(&response);
     Ok(response.client_id)
 }
 
-async fn update_status(
-    client: &mut GameControlClient<Channel>,
-    client_id: &str,
-    current_command: Option<&CurrentCommand>,
-    completed_command_id: Option<String>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+// 构造状态指标，包括当前命令的信息
+fn status_metrics(current_command: Option<&CurrentCommand>) -> HashMap<String, String> {
     let mut metrics = HashMap::new();
     metrics.insert("status".to_string(), "running".to_string());
     metrics.insert("memory_usage".to_string(), "128MB".to_string());
     metrics.insert("cpu_usage".to_string(), "25%".to_string());
 
-    // 如果有已完成的命令，添加到指标中
-    if let Some(command_id) = completed_command_id {
-        println!("\n[Command Completed] ----------------------------------------");
-        println!("Command ID: {}", command_id);
-        metrics.insert("completed_command_id".to_string(), command_id);
-    }
+    // 空闲玩家数：最大玩家数减去当前命令使用的玩家数
+    let busy_players = current_command
+        .and_then(|cmd| cmd.parameters.get("player_count"))
+        .and_then(|count| count.parse::<u32>().ok())
+        .unwrap_or(0);
+    metrics.insert("max_idle_players".to_string(), MAX_PLAYERS.saturating_sub(busy_players).to_string());
 
     // 如果有当前命令，添加命令相关的指标
     if let Some(cmd) = current_command {
@@ -145,7 +342,26 @@ async fn update_status(
         }
     }
 
-    let request = Request::new(StatusUpdate {
+    metrics
+}
+
+async fn update_status(
+    client: &mut GameControlClient<Channel>,
+    state: &Arc<Mutex<ClientState>>,
+    client_id: &str,
+    current_command: Option<&CurrentCommand>,
+    completed_command_id: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut metrics = status_metrics(current_command);
+
+    // 如果有已完成的命令，添加到指标中
+    if let Some(command_id) = completed_command_id {
+        println!("\n[Command Completed] ----------------------------------------");
+        println!("Command ID: {}", command_id);
+        metrics.insert("completed_command_id".to_string(), command_id);
+    }
+
+    let request = state.lock().unwrap().request(StatusUpdate {
         client_id: client_id.to_string(),
         metrics: metrics.clone(),
     });
@@ -163,32 +379,72 @@ async fn update_status(
     Ok(())
 }
 
-async fn get_status(
+// 订阅服务器的命令推送，直到流被关闭
+async fn watch_commands(
     client: &mut GameControlClient<Channel>,
     client_id: &str,
-) -> Result<Option<CurrentCommand>, Box<dyn std::error::Error + Send + Sync>> {
-    let request = Request::new(StatusRequest {
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let request = state.lock().unwrap().request(StatusRequest {
         client_id: client_id.to_string(),
     });
 
-    println!("\n[Requesting Status] ----------------------------------------");
+    println!("\n[Watching Commands] ----------------------------------------");
     println!("Client ID: {}", client_id);
 
-    let response = client.get_status(request).await?;
-    let response = response.into_inner();
-    
-    if let Some(cmd) = &response.current_command {
+    let mut stream = client.watch_commands(request).await?.into_inner();
+
+    while let Some(cmd) = stream.message().await? {
         println!("\n[Received Command] ----------------------------------------");
         println!("Command ID: {}", cmd.command_id);
         println!("Command: {}", cmd.command);
-        println!("Started At: {}", cmd.started_at);
+        println!("Created At: {}", cmd.created_at);
         println!("Parameters:");
         for (key, value) in &cmd.parameters {
             println!("  {}: {}", key, value);
         }
+
+        let mut state = state.lock().unwrap();
+        if state.current_command.is_none() {
+            state.start_command(CurrentCommand {
+                command_id: cmd.command_id,
+                command: cmd.command,
+                parameters: cmd.parameters,
+                started_at: cmd.created_at,
+            });
+        }
+        state.last_update = Utc::now().timestamp();
     }
-    
-    Ok(response.current_command)
+
+    println!("\n[Command Stream Closed] ----------------------------------------");
+    Ok(())
+}
+
+// WatchCommands 流只下发命令，旧版接口通过 GetStatus 轮询取消请求，中止后上报已取消的结果
+async fn poll_cancellations(
+    client: &mut GameControlClient<Channel>,
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let request = {
+        let state = state.lock().unwrap();
+        state.request(StatusRequest {
+            client_id: state.client_id.clone().unwrap_or_default(),
+        })
+    };
+    let response = client.get_status(request).await?.into_inner();
+
+    for cancel in response.cancellations {
+        let Some(result) = state.lock().unwrap().abort_command(&cancel.command_id) else {
+            continue;
+        };
+        println!("\n[Command Cancelled] ----------------------------------------");
+        println!("Command ID: {}", cancel.command_id);
+        println!("Reason: {}", cancel.reason);
+        println!("Aborted current command");
+        let request = state.lock().unwrap().request(result);
+        client.report_command_result(request).await?;
+    }
+    Ok(())
 }
 
 async fn handle_error(
@@ -202,15 +458,15 @@ async fn handle_error(
         println!("Lost connection to server, attempting to reconnect...");
         
         // 尝试重新连接
-        match connect_with_retry().await {
+        match connect_with_retry(state).await {
             Ok(new_client) => {
                 *client = new_client;
-                // 重新注册
-                match register_client(client).await {
+                // 重新注册，服务器会尽量恢复原来的客户端ID
+                match register_client(client, state).await {
                     Ok(new_client_id) => {
                         let mut state = state.lock().unwrap();
                         state.client_id = Some(new_client_id.clone());
-                        println!("Successfully reconnected and registered with new client ID: {}", new_client_id);
+                        println!("Successfully reconnected and registered with client ID: {}", new_client_id);
                         Ok(())
                     }
                     Err(e) => {
@@ -225,18 +481,25 @@ async fn handle_error(
             }
         }
     }
-    // 检查是否是 "Client not found" 错误
+    // 检查是否是 "Client not found" 错误，或者会话密钥已经失效（例如客户端被删除）
     else if let Some(status) = error.downcast_ref::<Status>() {
-        if status.code() == tonic::Code::NotFound && status.message() == "Client not found" {
+        let not_found = status.code() == tonic::Code::NotFound && status.message() == "Client not found";
+        let invalid_credentials =
+            status.code() == tonic::Code::Unauthenticated && status.message() == "Invalid client credentials";
+        if not_found || invalid_credentials {
+            // 失效的会话密钥无法恢复原有身份，只能重新注册为新的客户端
+            if invalid_credentials {
+                state.lock().unwrap().session_secret = None;
+            }
             println!("\n[Reconnecting] ----------------------------------------");
             println!("Server does not recognize client, re-registering...");
             
-            // 重新注册
-            match register_client(client).await {
+            // 重新注册，服务器会尽量恢复原来的客户端ID
+            match register_client(client, state).await {
                 Ok(new_client_id) => {
                     let mut state = state.lock().unwrap();
                     state.client_id = Some(new_client_id.clone());
-                    println!("Successfully re-registered with new client ID: {}", new_client_id);
+                    println!("Successfully re-registered with client ID: {}", new_client_id);
                     Ok(())
                 }
                 Err(e) => {
@@ -252,63 +515,244 @@ async fn handle_error(
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("\n[Starting Test Client] ----------------------------------------");
-    println!("Connecting to server at http://127.0.0.1:50051");
+// 按 NTP 的方式测量与服务器的时钟偏差和往返时间，同时上报上一次的测量结果
+async fn sync_time(
+    client: &mut GameControlClient<Channel>,
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<(), Status> {
+    let request = {
+        let state = state.lock().unwrap();
+        state.request(TimeSyncRequest {
+            client_id: state.client_id.clone().unwrap_or_default(),
+            client_send_us: Utc::now().timestamp_micros(),
+            offset_us: state.clock_offset_us,
+            rtt_us: state.rtt_us,
+        })
+    };
+    let response = client.sync_time(request).await?.into_inner();
+    let client_receive_us = Utc::now().timestamp_micros();
 
-    let state = Arc::new(Mutex::new(ClientState::new()));
-    
+    let rtt_us = (client_receive_us - response.client_send_us)
+        - (response.server_send_us - response.server_receive_us);
+    let offset_us = ((response.server_receive_us - response.client_send_us)
+        + (response.server_send_us - client_receive_us)) / 2;
+
+    let mut state = state.lock().unwrap();
+    if state.clock_offset_us.is_none() {
+        println!("\n[Time Sync] ----------------------------------------");
+        println!("Clock Offset: {} us", offset_us);
+        println!("RTT: {} us", rtt_us);
+    }
+    state.clock_offset_us = Some(offset_us);
+    state.rtt_us = Some(rtt_us);
+    Ok(())
+}
+
+// 通过一次双向会话完成注册、心跳、状态上报和命令接收，直到会话结束
+async fn run_session(
+    client: &mut GameControlClient<Channel>,
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let register = register_request(&state.lock().unwrap());
+
+    let (tx, rx) = mpsc::channel(16);
+    tx.send(ClientMessage {
+        payload: Some(client_message::Payload::Register(register)),
+    }).await?;
+
+    let request = state.lock().unwrap().request(ReceiverStream::new(rx));
+    let mut inbound = client.session(request).await?.into_inner();
+    let mut heartbeat = time::interval(Duration::from_secs(1));
+    let mut status = time::interval(Duration::from_secs(1));
+    let mut time_sync = time::interval(Duration::from_secs(TIME_SYNC_INTERVAL_SECS));
+    let mut sync_client = client.clone();
+    // 等待中的屏障命令的开始时间
+    let mut barrier_start: Option<(time::Instant, String, i64)> = None;
+
+    loop {
+        tokio::select! {
+            message = inbound.message() => {
+                let Some(message) = message? else {
+                    println!("\n[Session Closed] ----------------------------------------");
+                    return Ok(());
+                };
+                match message.payload {
+                    Some(server_message::Payload::Registered(response)) => {
+                        println!("\n[Register Response] ----------------------------------------");
+                        println!("Success: {}", response.success);
+                        println!("Client ID: {}", response.client_id);
+                        println!("Resumed: {}", response.resumed);
+                        println!("Server Protocol Version: {}", response.protocol_version);
+                        println!("Message: {}", response.message);
+                        state.lock().unwrap().registered(&response);
+                    }
+                    Some(server_message::Payload::Command(cmd)) => {
+                        println!("\n[Received Command] ----------------------------------------");
+                        println!("Command ID: {}", cmd.command_id);
+                        println!("Command: {}", cmd.command);
+                        println!("Created At: {}", cmd.created_at);
+
+                        let command_id = cmd.command_id.clone();
+                        let current = CurrentCommand {
+                            command_id: cmd.command_id,
+                            command: cmd.command,
+                            parameters: cmd.parameters,
+                            started_at: cmd.created_at,
+                        };
+                        let ack = {
+                            let mut state = state.lock().unwrap();
+                            state.last_update = Utc::now().timestamp();
+                            if state.current_command.is_some() {
+                                false
+                            } else if cmd.barrier {
+                                // 屏障命令先确认，等收到开始时间后再执行
+                                state.waiting_command = Some(current);
+                                true
+                            } else {
+                                state.start_command(current);
+                                false
+                            }
+                        };
+                        if ack {
+                            println!("Barrier command, waiting for start time");
+                            tx.send(ClientMessage {
+                                payload: Some(client_message::Payload::Ack(CommandAck { command_id })),
+                            }).await?;
+                        }
+                    }
+                    Some(server_message::Payload::Start(start)) => {
+                        println!("\n[Barrier Start] ----------------------------------------");
+                        println!("Command ID: {}", start.command_id);
+                        println!("Start At: {} ms", start.start_at_ms);
+                        // 开始时间是服务器时钟，按时钟偏差换算成本地等待时间
+                        let delay_ms = (start.start_at_ms - state.lock().unwrap().server_now_ms()).max(0) as u64;
+                        barrier_start = Some((
+                            time::Instant::now() + Duration::from_millis(delay_ms),
+                            start.command_id,
+                            start.start_at_ms,
+                        ));
+                    }
+                    Some(server_message::Payload::Cancel(cancel)) => {
+                        println!("\n[Command Cancelled] ----------------------------------------");
+                        println!("Command ID: {}", cancel.command_id);
+                        println!("Reason: {}", cancel.reason);
+                        let result = state.lock().unwrap().abort_command(&cancel.command_id);
+                        if let Some(result) = result {
+                            println!("Aborted current command");
+                            tx.send(ClientMessage {
+                                payload: Some(client_message::Payload::CommandResult(result)),
+                            }).await?;
+                        }
+                    }
+                    Some(server_message::Payload::Config(config)) => {
+                        println!("\n[Session Config] ----------------------------------------");
+                        println!("Heartbeat Interval: {}s", config.heartbeat_interval_secs);
+                        println!("Status Interval: {}s", config.status_interval_secs);
+                        heartbeat = time::interval(Duration::from_secs(config.heartbeat_interval_secs.max(1).into()));
+                        status = time::interval(Duration::from_secs(config.status_interval_secs.max(1).into()));
+                    }
+                    None => {}
+                }
+            }
+            _ = time::sleep_until(barrier_start.as_ref().map_or_else(time::Instant::now, |(at, _, _)| *at)), if barrier_start.is_some() => {
+                let Some((_, command_id, start_at_ms)) = barrier_start.take() else {
+                    continue;
+                };
+                if !state.lock().unwrap().start_waiting(&command_id) {
+                    continue;
+                }
+                let started_at_ms = state.lock().unwrap().server_now_ms();
+                println!("\n[Barrier Command Started] ----------------------------------------");
+                println!("Command ID: {}", command_id);
+                println!("Offset From Start Time: {} ms", started_at_ms - start_at_ms);
+                tx.send(ClientMessage {
+                    payload: Some(client_message::Payload::Started(CommandStarted { command_id, started_at_ms })),
+                }).await?;
+            }
+            _ = time_sync.tick() => {
+                if let Err(e) = sync_time(&mut sync_client, state).await {
+                    println!("Time sync failed: {}", e);
+                }
+            }
+            _ = heartbeat.tick() => {
+                tx.send(ClientMessage {
+                    payload: Some(client_message::Payload::Heartbeat(Heartbeat {
+                        timestamp: Utc::now().timestamp(),
+                    })),
+                }).await?;
+            }
+            _ = status.tick() => {
+                let (client_id, current_command, result) = {
+                    let mut state = state.lock().unwrap();
+                    let result = if state.is_command_finished() {
+                        state.finish_command()
+                    } else {
+                        None
+                    };
+                    (state.client_id.clone(), state.current_command.clone(), result)
+                };
+                let Some(client_id) = client_id else {
+                    continue;
+                };
+
+                if let Some(result) = result {
+                    println!("\n[Command Completed] ----------------------------------------");
+                    println!("Command ID: {}", result.command_id);
+                    println!("Success: {}", result.success);
+                    tx.send(ClientMessage {
+                        payload: Some(client_message::Payload::CommandResult(result)),
+                    }).await?;
+                }
+
+                tx.send(ClientMessage {
+                    payload: Some(client_message::Payload::Status(StatusUpdate {
+                        client_id,
+                        metrics: status_metrics(current_command.as_ref()),
+                    })),
+                }).await?;
+            }
+        }
+    }
+}
+
+// 使用旧版一元接口：Register、UpdateStatus 和 WatchCommands
+async fn run_legacy(state: Arc<Mutex<ClientState>>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // 连接到服务器（带重试）
-    let mut client = connect_with_retry().await?;
+    let mut client = connect_with_retry(&state).await?;
     
     // 注册客户端
-    let client_id = register_client(&mut client).await?;
+    let client_id = register_client(&mut client, &state).await?;
     state.lock().unwrap().client_id = Some(client_id.clone());
     
-    // 创建两个客户端实例，一个用于状态更新，一个用于命令处理
-    let status_client = connect_with_retry().await?;
-    let command_client = connect_with_retry().await?;
+    // 创建两个客户端实例，一个用于接收命令推送，一个用于状态更新
+    let watch_client = connect_with_retry(&state).await?;
+    let command_client = connect_with_retry(&state).await?;
     
-    println!("\n[Starting Status Update Loop] ----------------------------------------");
-    // 启动状态更新循环
-    let update_state = state.clone();
-    let status_handle = tokio::spawn(async move {
-        let mut client = status_client;
-        let mut interval = time::interval(Duration::from_secs(1));
+    println!("\n[Starting Command Watch Loop] ----------------------------------------");
+    // 启动命令订阅循环，流断开后重新订阅
+    let watch_state = state.clone();
+    let watch_handle = tokio::spawn(async move {
+        let mut client = watch_client;
         loop {
-            interval.tick().await;
-            
             let client_id = {
-                let state = update_state.lock().unwrap();
+                let state = watch_state.lock().unwrap();
                 state.client_id.clone()
             };
             
             if let Some(client_id) = client_id {
-                // 获取命令状态
-                match get_status(&mut client, &client_id).await {
-                    Ok(command) => {
-                        // 在获取锁的作用域内更新状态
-                        let mut state = update_state.lock().unwrap();
-                        if command.is_some() && state.current_command.is_none() {
-                            state.start_command(command.unwrap());
-                        }
-                        state.last_update = Utc::now().timestamp();
-                    }
-                    Err(e) => {
-                        // 处理错误，如果是连接错误或 "Client not found" 错误则重新连接或注册
-                        if let Err(e) = handle_error(e, &mut client, &update_state).await {
-                            println!("\n[Error] ----------------------------------------");
-                            println!("Failed to get status: {}", e);
-                            let mut state = update_state.lock().unwrap();
-                            state.reconnect_count += 1;
-                        }
+                if let Err(e) = watch_commands(&mut client, &client_id, &watch_state).await {
+                    // 处理错误，如果是连接错误或 "Client not found" 错误则重新连接或注册
+                    if let Err(e) = handle_error(e, &mut client, &watch_state).await {
+                        println!("\n[Error] ----------------------------------------");
+                        println!("Failed to watch commands: {}", e);
+                        let mut state = watch_state.lock().unwrap();
+                        state.reconnect_count += 1;
                     }
                 }
             }
             
-            // 短暂休眠以避免过于频繁的请求
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            // 等待一段时间后重新订阅
+            tokio::time::sleep(Duration::from_secs(1)).await;
         }
     });
     
@@ -336,6 +780,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 // 发送状态更新，此时已经释放了锁
                 if let Err(e) = update_status(
                     &mut client,
+                    &command_state,
                     &client_id,
                     current_command.as_ref(),
                     completed_command_id,
@@ -345,6 +790,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         println!("\n[Error] ----------------------------------------");
                         println!("Failed to update status: {}", e);
                     }
+                } else if let Err(e) = poll_cancellations(&mut client, &command_state).await {
+                    if let Err(e) = handle_error(e, &mut client, &command_state).await {
+                        println!("\n[Error] ----------------------------------------");
+                        println!("Failed to poll cancellations: {}", e);
+                    }
                 }
             }
             
@@ -353,16 +803,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
     });
     
-    println!("\n[Ready] ----------------------------------------");
-    println!("Press Ctrl+C to exit...");
-    
-    // 等待两个任务完成或者用户按下 Ctrl+C
+    // 等待任意一个任务结束
     tokio::select! {
-        status_result = status_handle => {
-            if let Err(e) = status_result {
-                println!("Status update loop ended with error: {}", e);
+        watch_result = watch_handle => {
+            if let Err(e) = watch_result {
+                println!("Command watch loop ended with error: {}", e);
             } else {
-                println!("Status update loop ended");
+                println!("Command watch loop ended");
             }
         }
         command_result = command_handle => {
@@ -372,11 +819,118 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 println!("Command processing loop ended");
             }
         }
+    }
+    
+    Ok(())
+}
+
+// 根据命令行参数构造服务器地址，指定了 CA 或客户端证书时使用 TLS
+fn server_endpoint(args: &[String]) -> Result<Endpoint, Box<dyn std::error::Error + Send + Sync>> {
+    let option = |name: &str| args.iter().skip_while(|arg| *arg != name).nth(1).cloned();
+    let ca = option("--ca");
+    let cert = option("--cert");
+    let key = option("--key");
+    let tls = ca.is_some() || cert.is_some() || key.is_some();
+
+    let default_url = if tls { "https://127.0.0.1:50051" } else { "http://127.0.0.1:50051" };
+    let mut endpoint = Endpoint::from_shared(option("--server").unwrap_or_else(|| default_url.to_string()))?;
+    if !tls {
+        return Ok(endpoint);
+    }
+
+    let mut config = ClientTlsConfig::new();
+    if let Some(ca) = ca {
+        config = config.ca_certificate(Certificate::from_pem(std::fs::read(ca)?));
+    }
+    match (cert, key) {
+        (Some(cert), Some(key)) => {
+            config = config.identity(Identity::from_pem(std::fs::read(cert)?, std::fs::read(key)?));
+        }
+        (None, None) => {}
+        _ => return Err("--cert and --key must be given together".into()),
+    }
+    if let Some(domain) = option("--domain") {
+        config = config.domain_name(domain);
+    }
+    endpoint = endpoint.tls_config(config)?;
+    Ok(endpoint)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 加上 --legacy 参数时使用旧版一元接口，否则使用双向会话
+    let legacy = std::env::args().any(|arg| arg == "--legacy");
+    // --instance-key <key> 指定客户端实例的持久标识，重启后重新注册时恢复原有身份
+    let instance_key = std::env::args()
+        .skip_while(|arg| arg != "--instance-key")
+        .nth(1)
+        .unwrap_or_default();
+    // --label key=value 可以出现多次，指定注册时上报的标签
+    let args: Vec<String> = std::env::args().collect();
+    let labels = args
+        .windows(2)
+        .filter(|pair| pair[0] == "--label")
+        .filter_map(|pair| pair[1].split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    // --enrollment-token <token> 指定注册令牌，服务器启用客户端认证时需要
+    let enrollment_token = std::env::args()
+        .skip_while(|arg| arg != "--enrollment-token")
+        .nth(1)
+        .unwrap_or_default();
+
+    // --server <url> 指定服务器地址
+    // --ca <pem> 指定信任的服务器 CA，--cert <pem> 和 --key <pem> 指定 mTLS 的客户端证书，
+    // --domain <name> 指定校验服务器证书时使用的名称，默认取服务器地址中的主机名
+    let endpoint = server_endpoint(&args)?;
+
+    println!("\n[Starting Test Client] ----------------------------------------");
+    println!("Connecting to server at {}", endpoint.uri());
+    println!("Mode: {}", if legacy { "legacy" } else { "session" });
+
+    let state = Arc::new(Mutex::new(ClientState::new(endpoint, instance_key, labels, enrollment_token)));
+
+    let run_state = state.clone();
+    let run_handle = tokio::spawn(async move {
+        if legacy {
+            return run_legacy(run_state).await;
+        }
+
+        // 会话断开后重新连接，并以之前的客户端ID重新注册
+        loop {
+            let mut client = connect_with_retry(&run_state).await?;
+            if let Err(e) = run_session(&mut client, &run_state).await {
+                println!("\n[Session Error] ----------------------------------------");
+                println!("{}", e);
+                // 会话密钥失效（例如客户端被删除）时不再携带，下次注册为新的客户端
+                let invalid_credentials = e.downcast_ref::<Status>().is_some_and(|status| {
+                    status.code() == tonic::Code::Unauthenticated && status.message() == "Invalid client credentials"
+                });
+                if invalid_credentials {
+                    run_state.lock().unwrap().session_secret = None;
+                }
+                run_state.lock().unwrap().reconnect_count += 1;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    println!("\n[Ready] ----------------------------------------");
+    println!("Press Ctrl+C to exit...");
+
+    tokio::select! {
+        run_result = run_handle => {
+            match run_result {
+                Ok(Err(e)) => println!("Client ended with error: {}", e),
+                Err(e) => println!("Client task failed: {}", e),
+                Ok(Ok(())) => println!("Client ended"),
+            }
+        }
         _ = tokio::signal::ctrl_c() => {
             println!("\n[Shutting down] ----------------------------------------");
             println!("Received Ctrl+C, shutting down...");
         }
     }
-    
+
     Ok(())
 }
@@ -0,0 +1,341 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// 命令目录，从 TOML 配置文件加载
+// 声明可用的命令、参数类型和支持的客户端类型，发送命令时据此校验
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default)]
+    pub commands: Vec<CommandSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // 支持该命令的客户端类型，为空表示所有类型都支持
+    #[serde(default)]
+    pub client_types: Vec<String>,
+    #[serde(default)]
+    pub parameters: Vec<ParameterSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "type")]
+    pub kind: ParameterType,
+    // 未提供且没有默认值时拒绝命令
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<Scalar>,
+    // 数值参数的取值范围（包含边界）
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    // 枚举参数的可选值，字符串参数指定时同样只能取这些值
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Enum,
+}
+
+impl FromStr for ParameterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(ParameterType::String),
+            "integer" => Ok(ParameterType::Integer),
+            "float" => Ok(ParameterType::Float),
+            "boolean" => Ok(ParameterType::Boolean),
+            "enum" => Ok(ParameterType::Enum),
+            _ => Err(format!("Unknown parameter type '{}'", s)),
+        }
+    }
+}
+
+// 配置文件中的标量值，命令参数最终都以字符串下发
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scalar::Boolean(value) => write!(f, "{}", value),
+            Scalar::Integer(value) => write!(f, "{}", value),
+            Scalar::Float(value) => write!(f, "{}", value),
+            Scalar::String(value) => f.write_str(value),
+        }
+    }
+}
+
+impl Catalog {
+    // 加载并检查目录文件，默认值不符合参数定义时视为配置错误
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let catalog: Catalog = toml::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        catalog
+            .check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        Ok(catalog)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        for command in &self.commands {
+            if !names.insert(command.name.as_str()) {
+                return Err(format!("Duplicate command '{}'", command.name));
+            }
+            let mut parameters = HashSet::new();
+            for parameter in &command.parameters {
+                if !parameters.insert(parameter.name.as_str()) {
+                    return Err(format!("Duplicate parameter '{}' in command '{}'", parameter.name, command.name));
+                }
+                if parameter.kind == ParameterType::Enum && parameter.values.is_empty() {
+                    return Err(format!("Enum parameter '{}' in command '{}' has no values", parameter.name, command.name));
+                }
+                if let Some(default) = &parameter.default {
+                    parameter
+                        .check(&default.to_string())
+                        .map_err(|e| format!("Invalid default in command '{}': {}", command.name, e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    // 校验发送给指定类型客户端的命令，并补全参数默认值
+    pub fn validate(
+        &self,
+        client_type: &str,
+        command: &str,
+        parameters: &mut HashMap<String, String>,
+    ) -> Result<(), String> {
+        let spec = self.get(command).ok_or_else(|| format!("Unknown command '{}'", command))?;
+        if !spec.supports(client_type) {
+            return Err(format!("Command '{}' is not supported by client type '{}'", command, client_type));
+        }
+
+        if let Some(name) = parameters.keys().find(|name| spec.parameter(name).is_none()) {
+            return Err(format!("Unknown parameter '{}' for command '{}'", name, command));
+        }
+
+        for parameter in &spec.parameters {
+            match parameters.get(&parameter.name) {
+                Some(value) => parameter.check(value)?,
+                None => match &parameter.default {
+                    Some(default) => {
+                        parameters.insert(parameter.name.clone(), default.to_string());
+                    }
+                    None if parameter.required => {
+                        return Err(format!("Missing required parameter '{}'", parameter.name));
+                    }
+                    None => {}
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CommandSpec {
+    pub fn supports(&self, client_type: &str) -> bool {
+        self.client_types.is_empty() || self.client_types.iter().any(|supported| supported == client_type)
+    }
+
+    // 按参数定义检查已提供的参数值和必需参数，不补全默认值，也不拒绝未定义的参数
+    // 用于客户端自己声明的命令，命令目录的校验见 Catalog::validate
+    pub fn check_parameters(&self, parameters: &HashMap<String, String>) -> Result<(), String> {
+        for parameter in &self.parameters {
+            match parameters.get(&parameter.name) {
+                Some(value) => parameter.check(value)?,
+                None if parameter.required && parameter.default.is_none() => {
+                    return Err(format!("Missing required parameter '{}'", parameter.name));
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn parameter(&self, name: &str) -> Option<&ParameterSpec> {
+        self.parameters.iter().find(|parameter| parameter.name == name)
+    }
+}
+
+impl ParameterSpec {
+    // 检查参数值的类型、范围和可选值
+    fn check(&self, value: &str) -> Result<(), String> {
+        let number = match self.kind {
+            ParameterType::Integer => Some(
+                value
+                    .parse::<i64>()
+                    .map_err(|_| format!("Parameter '{}' must be an integer, got '{}'", self.name, value))?
+                    as f64,
+            ),
+            // NaN 与任何边界比较都不成立，无穷大也没有意义，一律拒绝
+            ParameterType::Float => Some(
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| format!("Parameter '{}' must be a finite number, got '{}'", self.name, value))?,
+            ),
+            ParameterType::Boolean => {
+                if value != "true" && value != "false" {
+                    return Err(format!("Parameter '{}' must be true or false, got '{}'", self.name, value));
+                }
+                None
+            }
+            ParameterType::String | ParameterType::Enum => None,
+        };
+
+        if let Some(number) = number {
+            if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max) {
+                return Err(format!(
+                    "Parameter '{}' must be between {} and {}, got {}",
+                    self.name,
+                    self.min.map_or("-inf".to_string(), |min| min.to_string()),
+                    self.max.map_or("inf".to_string(), |max| max.to_string()),
+                    value
+                ));
+            }
+        }
+
+        if !self.values.is_empty() && !self.values.iter().any(|allowed| allowed == value) {
+            return Err(format!(
+                "Parameter '{}' must be one of [{}], got '{}'",
+                self.name,
+                self.values.join(", "),
+                value
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = r#"
+[[commands]]
+name = "start_test"
+client_types = ["load_test"]
+
+[[commands.parameters]]
+name = "players"
+type = "integer"
+required = true
+min = 1
+max = 100
+
+[[commands.parameters]]
+name = "mode"
+type = "enum"
+values = ["fast", "slow"]
+default = "fast"
+
+[[commands.parameters]]
+name = "verbose"
+type = "boolean"
+
+[[commands.parameters]]
+name = "speed"
+type = "float"
+min = 0.1
+max = 10.0
+
+[[commands.parameters]]
+name = "ratio"
+type = "float"
+"#;
+
+    fn catalog() -> Catalog {
+        let catalog: Catalog = toml::from_str(CATALOG).unwrap();
+        catalog.check().unwrap();
+        catalog
+    }
+
+    fn parameters(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn fills_in_defaults() {
+        let mut values = parameters(&[("players", "10")]);
+        catalog().validate("load_test", "start_test", &mut values).unwrap();
+        assert_eq!(values["mode"], "fast");
+        assert!(!values.contains_key("verbose"));
+
+        let mut values = parameters(&[("players", "10"), ("speed", "2.5"), ("ratio", "-1e3")]);
+        catalog().validate("load_test", "start_test", &mut values).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_or_unsupported_commands() {
+        let catalog = catalog();
+        let mut values = parameters(&[("players", "10")]);
+        assert!(catalog.validate("load_test", "stop_test", &mut values).is_err());
+        assert!(catalog.validate("monitor", "start_test", &mut values).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        let catalog = catalog();
+        for values in [
+            &[][..],
+            &[("players", "ten")],
+            &[("players", "0")],
+            &[("players", "101")],
+            &[("players", "10"), ("mode", "medium")],
+            &[("players", "10"), ("verbose", "yes")],
+            &[("players", "10"), ("region", "eu")],
+            &[("players", "10"), ("speed", "NaN")],
+            &[("players", "10"), ("speed", "20")],
+            &[("players", "10"), ("ratio", "inf")],
+            &[("players", "10"), ("ratio", "-infinity")],
+        ] {
+            let mut values = parameters(values);
+            assert!(catalog.validate("load_test", "start_test", &mut values).is_err(), "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn check_rejects_invalid_definitions() {
+        let duplicate = format!("{}\n[[commands]]\nname = \"start_test\"\n", CATALOG);
+        assert!(toml::from_str::<Catalog>(&duplicate).unwrap().check().is_err());
+        let bad_default = CATALOG.replace("default = \"fast\"", "default = \"medium\"");
+        assert!(toml::from_str::<Catalog>(&bad_default).unwrap().check().is_err());
+        let no_values = CATALOG.replace("values = [\"fast\", \"slow\"]\ndefault = \"fast\"", "");
+        assert!(toml::from_str::<Catalog>(&no_values).unwrap().check().is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// 服务器配置
+// 默认值适用于本地开发，部署时通过 ROBOT_ADMIN_* 环境变量覆盖
+#[derive(Debug, Clone)]
+pub struct Config {
+    // gRPC 和网页服务的监听地址
+    pub grpc_addr: SocketAddr,
+    pub web_addr: SocketAddr,
+    // 服务器证书和私钥（PEM），同时设置时两个监听地址都使用 TLS
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    // 签发客户端证书的 CA（PEM），设置后 gRPC 连接必须出示由它签发的证书
+    pub grpc_client_ca_path: Option<PathBuf>,
+    // 命令的默认执行超时时间（秒），0 表示不超时
+    // 发送命令时指定的 timeout_secs 优先
+    pub default_command_timeout_secs: u32,
+    // 持久化数据目录，未设置时状态只保存在内存中
+    pub data_dir: Option<PathBuf>,
+    // 客户端超过多久（毫秒）没有消息时标记为 Stale
+    pub client_stale_after_ms: i64,
+    // 客户端超过多久（毫秒）没有消息时标记为 Offline，并取消其队列中的命令
+    pub client_offline_after_ms: i64,
+    // 客户端超过多久（秒）没有消息时标记为 Expired，之后重新注册不再恢复原有身份
+    pub client_expire_after_secs: i64,
+    // 命令目录文件，未设置时不校验命令名称和参数
+    pub catalog_path: Option<PathBuf>,
+    // 用户和 API 令牌文件，未设置时网页接口不需要登录
+    pub auth_path: Option<PathBuf>,
+    // 客户端数值指标原始数据点的保留时间（秒）
+    pub metrics_raw_retention_secs: i64,
+    // 客户端数值指标按分钟聚合后的保留时间（秒）
+    pub metrics_retention_secs: i64,
+    // 由 /metrics 导出的客户端状态指标名称，限制时间序列的数量
+    pub prometheus_client_metrics: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            grpc_addr: SocketAddr::from(([127, 0, 0, 1], 50051)),
+            web_addr: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            tls_cert_path: None,
+            tls_key_path: None,
+            grpc_client_ca_path: None,
+            default_command_timeout_secs: 0,
+            data_dir: None,
+            client_stale_after_ms: 3000,
+            client_offline_after_ms: 10000,
+            client_expire_after_secs: 3600,
+            catalog_path: None,
+            auth_path: None,
+            metrics_raw_retention_secs: 3600,
+            metrics_retention_secs: 86400,
+            prometheus_client_metrics: vec![
+                "cpu_usage".to_string(),
+                "memory_usage".to_string(),
+                "max_idle_players".to_string(),
+            ],
+        }
+    }
+}
+
+impl Config {
+    // 从环境变量读取配置，未设置或无法解析的项使用默认值
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            grpc_addr: env_or("ROBOT_ADMIN_GRPC_ADDR", defaults.grpc_addr),
+            web_addr: env_or("ROBOT_ADMIN_WEB_ADDR", defaults.web_addr),
+            tls_cert_path: env::var_os("ROBOT_ADMIN_TLS_CERT").map(PathBuf::from),
+            tls_key_path: env::var_os("ROBOT_ADMIN_TLS_KEY").map(PathBuf::from),
+            grpc_client_ca_path: env::var_os("ROBOT_ADMIN_GRPC_CLIENT_CA").map(PathBuf::from),
+            default_command_timeout_secs: env_or(
+                "ROBOT_ADMIN_COMMAND_TIMEOUT_SECS",
+                defaults.default_command_timeout_secs,
+            ),
+            data_dir: env::var_os("ROBOT_ADMIN_DATA_DIR").map(PathBuf::from),
+            client_stale_after_ms: env_or("ROBOT_ADMIN_CLIENT_STALE_MS", defaults.client_stale_after_ms),
+            client_offline_after_ms: env_or("ROBOT_ADMIN_CLIENT_OFFLINE_MS", defaults.client_offline_after_ms),
+            client_expire_after_secs: env_or(
+                "ROBOT_ADMIN_CLIENT_EXPIRE_SECS",
+                defaults.client_expire_after_secs,
+            ),
+            catalog_path: env::var_os("ROBOT_ADMIN_CATALOG").map(PathBuf::from),
+            auth_path: env::var_os("ROBOT_ADMIN_AUTH").map(PathBuf::from),
+            metrics_raw_retention_secs: env_or(
+                "ROBOT_ADMIN_METRICS_RAW_SECS",
+                defaults.metrics_raw_retention_secs,
+            ),
+            metrics_retention_secs: env_or(
+                "ROBOT_ADMIN_METRICS_RETENTION_SECS",
+                defaults.metrics_retention_secs,
+            ),
+            prometheus_client_metrics: env::var("ROBOT_ADMIN_PROMETHEUS_CLIENT_METRICS")
+                .map(|names| {
+                    names
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or(defaults.prometheus_client_metrics),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            println!("Ignoring invalid value for {}: {}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
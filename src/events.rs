@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::grpc::{ClientState, Command, CommandStatus};
+
+// 事件总线的缓冲区大小，订阅者落后更多时会丢失事件
+pub const EVENT_BUFFER: usize = 1024;
+
+// 服务器内部状态变化的通知，通过 GET /api/events 推送给网页
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    // 新注册或恢复了原有身份的客户端
+    ClientRegistered {
+        client_id: String,
+        name: String,
+        client_type: String,
+        resumed: bool,
+    },
+    // 客户端上报了新的状态指标
+    ClientStatus {
+        client_id: String,
+        metrics: HashMap<String, String>,
+        max_idle_players: Option<u32>,
+        last_seen: i64,
+    },
+    // 客户端生命周期状态变化（断开连接除外）
+    ClientStateChanged {
+        client_id: String,
+        state: ClientState,
+    },
+    // 客户端断开连接或长时间没有消息，不再接收命令
+    ClientDisconnected {
+        client_id: String,
+        state: ClientState,
+    },
+    ClientRemoved {
+        client_id: String,
+    },
+    // 命令创建或状态变化
+    CommandChanged {
+        command_id: String,
+        client_id: String,
+        command: String,
+        status: CommandStatus,
+        cancelling: bool,
+        batch_id: Option<String>,
+    },
+    EmergencyStop {
+        active: bool,
+        reason: Option<String>,
+    },
+}
+
+impl Event {
+    pub fn command_changed(command_id: &str, command: &Command) -> Self {
+        Event::CommandChanged {
+            command_id: command_id.to_string(),
+            client_id: command.client_id.clone(),
+            command: command.command.clone(),
+            status: command.status.clone(),
+            cancelling: command.is_cancelling(),
+            batch_id: command.batch_id.clone(),
+        }
+    }
+
+    // 事件类型，与序列化后的 type 字段一致
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::ClientRegistered { .. } => "client_registered",
+            Event::ClientStatus { .. } => "client_status",
+            Event::ClientStateChanged { .. } => "client_state_changed",
+            Event::ClientDisconnected { .. } => "client_disconnected",
+            Event::ClientRemoved { .. } => "client_removed",
+            Event::CommandChanged { .. } => "command_changed",
+            Event::EmergencyStop { .. } => "emergency_stop",
+        }
+    }
+
+    // 事件相关的客户端，紧急停止这类全局事件没有
+    pub fn client_id(&self) -> Option<&str> {
+        match self {
+            Event::ClientRegistered { client_id, .. }
+            | Event::ClientStatus { client_id, .. }
+            | Event::ClientStateChanged { client_id, .. }
+            | Event::ClientDisconnected { client_id, .. }
+            | Event::ClientRemoved { client_id }
+            | Event::CommandChanged { client_id, .. } => Some(client_id),
+            Event::EmergencyStop { .. } => None,
+        }
+    }
+}
+
+// 订阅时的过滤条件，未指定的条件不过滤
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub kinds: Vec<String>,
+    pub client_id: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|kind| kind == event.kind()) {
+            return false;
+        }
+        match (&self.client_id, event.client_id()) {
+            (Some(wanted), Some(client_id)) => wanted == client_id,
+            // 指定客户端时仍然推送全局事件
+            _ => true,
+        }
+    }
+}
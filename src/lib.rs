@@ -1,2 +1,13 @@
+pub mod auth;
+pub mod catalog;
+pub mod config;
+pub mod events;
 pub mod grpc;
+pub mod prometheus;
+pub mod schedule;
+pub mod selector;
+pub mod storage;
+pub mod timeseries;
+pub mod tls;
 pub mod web;
+pub mod workflow;
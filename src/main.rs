@@ -1,27 +1,56 @@
-mod grpc;
-mod web;
-
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::serve;
+use robot_admin::config::Config;
 use robot_admin::grpc::GameControlService;
 use robot_admin::grpc::game_control::game_control_server::GameControlServer;
+use robot_admin::prometheus::RpcMetricsLayer;
+use robot_admin::storage::FileStorage;
+use robot_admin::tls;
 use tonic::transport::Server as TonicServer;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Create the gRPC service
-    let game_service = Arc::new(GameControlService::new());
+    // Load configuration from ROBOT_ADMIN_* environment variables
+    let config = Config::from_env();
+    let grpc_addr = config.grpc_addr;
+    let web_addr = config.web_addr;
+
+    // Load TLS settings before the config moves into the service so bad files fail at startup
+    let grpc_tls = tls::grpc_config(&config)?;
+    let web_tls = tls::web_config(&config).await?;
+    let client_ca = config.grpc_client_ca_path.is_some();
+
+    // Create the gRPC service, restoring persisted state when a data directory is configured
+    let game_service = match config.data_dir.clone() {
+        Some(data_dir) => {
+            println!("Persisting state in {}", data_dir.display());
+            let storage = FileStorage::open(&data_dir)?;
+            Arc::new(GameControlService::with_storage(config, Arc::new(storage))?)
+        }
+        None => Arc::new(GameControlService::with_config(config)),
+    };
     let grpc_service = game_service.clone();
 
     // Start the gRPC server
-    let grpc_addr = "127.0.0.1:50051".parse()?;
-    println!("Starting gRPC server on {}", grpc_addr);
-    
+    println!(
+        "Starting gRPC server on {}{}",
+        grpc_addr,
+        match (&grpc_tls, client_ca) {
+            (Some(_), true) => " (TLS, client certificates required)",
+            (Some(_), false) => " (TLS)",
+            (None, _) => "",
+        }
+    );
+    let mut grpc_server = TonicServer::builder();
+    if let Some(tls_config) = grpc_tls {
+        grpc_server = grpc_server.tls_config(tls_config)?;
+    }
+
     tokio::spawn(async move {
-        TonicServer::builder()
-            .add_service(GameControlServer::new(grpc_service))
+        grpc_server
+            .layer(RpcMetricsLayer::new(grpc_service.stats()))
+            .add_service(GameControlServer::with_interceptor(grpc_service.clone(), grpc_service.grpc_authenticator()))
             .serve(grpc_addr)
             .await
             .unwrap();
@@ -31,13 +60,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app = robot_admin::web::router(game_service);
 
     // Start the web server
-    let web_addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    println!("Starting web server on {}", web_addr);
-    
-    serve(
-        tokio::net::TcpListener::bind(web_addr).await?,
-        app.into_make_service()
-    ).await?;
+    println!("Starting web server on {}{}", web_addr, if web_tls.is_some() { " (TLS)" } else { "" });
+
+    match web_tls {
+        Some(tls_config) => {
+            axum_server::bind_rustls(web_addr, tls_config)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        None => {
+            serve(
+                tokio::net::TcpListener::bind(web_addr).await?,
+                app.into_make_service()
+            ).await?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tonic::codegen::{http, BoxFuture, Service};
+use tower::Layer;
+
+use crate::grpc::{ClientState, CommandStatus, GameControlService};
+use crate::timeseries::parse_value;
+
+// RPC 耗时直方图的桶上限（秒）
+const LATENCY_BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+// GameControl 服务的 RPC 方法，其他路径的请求统一记录为 unknown，避免任意路径产生新的时间序列
+const RPC_METHODS: [&str; 11] = [
+    "SendCommand",
+    "Register",
+    "GetStatus",
+    "UpdateStatus",
+    "WatchCommands",
+    "Session",
+    "ReportCommandResult",
+    "CancelCommand",
+    "SetEmergencyStop",
+    "SendBatchCommand",
+    "SyncTime",
+];
+const RPC_PATH_PREFIX: &str = "/game_control.GameControl/";
+
+const CLIENT_STATES: [ClientState; 4] = [ClientState::Online, ClientState::Stale, ClientState::Offline, ClientState::Expired];
+
+const COMMAND_STATUSES: [CommandStatus; 6] = [
+    CommandStatus::Pending,
+    CommandStatus::Delivered,
+    CommandStatus::Completed,
+    CommandStatus::Failed,
+    CommandStatus::TimedOut,
+    CommandStatus::Cancelled,
+];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    // 每个桶内（不累加）的观测次数，最后一个是超过所有上限的次数
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let index = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[index] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+// 服务器运行过程中累计的计数，抓取时与当前状态一起导出
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    // 按 RPC 方法名记录的耗时，流式 RPC 记录的是建立流的耗时
+    rpc_latency: Mutex<BTreeMap<&'static str, Histogram>>,
+    // 后台任务因长时间没有消息而改变客户端状态的次数，按改变后的状态记录
+    evictions: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl ServerStats {
+    pub fn observe_rpc(&self, method: &'static str, elapsed: Duration) {
+        let mut latency = self.rpc_latency.lock().unwrap();
+        latency.entry(method).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_eviction(&self, state: ClientState) {
+        *self.evictions.lock().unwrap().entry(state.as_str()).or_default() += 1;
+    }
+}
+
+// 记录每次 gRPC 调用耗时的 tower 中间件
+#[derive(Clone)]
+pub struct RpcMetricsLayer {
+    stats: Arc<ServerStats>,
+}
+
+impl RpcMetricsLayer {
+    pub fn new(stats: Arc<ServerStats>) -> Self {
+        Self { stats }
+    }
+}
+
+impl<S> Layer<S> for RpcMetricsLayer {
+    type Service = RpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcMetrics { inner, stats: self.stats.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcMetrics<S> {
+    inner: S,
+    stats: Arc<ServerStats>,
+}
+
+impl<S, B> Service<http::Request<B>> for RpcMetrics<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let method = rpc_method(request.uri().path());
+        let stats = self.stats.clone();
+        let started = Instant::now();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            stats.observe_rpc(method, started.elapsed());
+            response
+        })
+    }
+}
+
+// 路径形如 /game_control.GameControl/Register
+fn rpc_method(path: &str) -> &'static str {
+    path.strip_prefix(RPC_PATH_PREFIX)
+        .and_then(|name| RPC_METHODS.iter().find(|method| **method == name))
+        .copied()
+        .unwrap_or("unknown")
+}
+
+// 按 Prometheus 文本格式输出
+struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// 导出服务器和客户端的指标
+pub async fn render(service: &GameControlService) -> String {
+    let mut exposition = Exposition { out: String::new() };
+    let clients = service.get_clients().await;
+
+    exposition.family("robot_admin_clients", "gauge", "Registered clients by type and state.");
+    let mut client_counts: BTreeMap<(&str, ClientState), usize> = BTreeMap::new();
+    let types: BTreeSet<&str> = clients.values().map(|client| client.client_type.as_str()).collect();
+    for client in clients.values() {
+        *client_counts.entry((client.client_type.as_str(), client.state)).or_default() += 1;
+    }
+    for client_type in types {
+        for state in CLIENT_STATES {
+            let count = client_counts.get(&(client_type, state)).copied().unwrap_or(0);
+            exposition.sample(
+                "robot_admin_clients",
+                &[("type", client_type), ("state", state.as_str())],
+                count as f64,
+            );
+        }
+    }
+
+    exposition.family("robot_admin_commands", "gauge", "Recorded commands by status.");
+    let command_counts = service.command_status_counts().await;
+    for status in COMMAND_STATUSES {
+        let count = command_counts.get(&status).copied().unwrap_or(0);
+        exposition.sample("robot_admin_commands", &[("status", status.as_str())], count as f64);
+    }
+
+    exposition.family("robot_admin_queued_commands", "gauge", "Commands waiting in client queues.");
+    let queued: usize = service.queue_lengths().await.values().sum();
+    exposition.sample("robot_admin_queued_commands", &[], queued as f64);
+
+    exposition.family("robot_admin_emergency_stop", "gauge", "Whether the fleet-wide emergency stop is engaged.");
+    let stopped = service.emergency_stop().await.is_some();
+    exposition.sample("robot_admin_emergency_stop", &[], if stopped { 1.0 } else { 0.0 });
+
+    let stats = service.stats();
+    exposition.family(
+        "robot_admin_reaper_evictions_total",
+        "counter",
+        "Clients moved to a worse state after going silent, by new state.",
+    );
+    {
+        let evictions = stats.evictions.lock().unwrap();
+        for state in &CLIENT_STATES[1..] {
+            let count = evictions.get(state.as_str()).copied().unwrap_or(0);
+            exposition.sample("robot_admin_reaper_evictions_total", &[("state", state.as_str())], count as f64);
+        }
+    }
+
+    exposition.family("robot_admin_rpc_duration_seconds", "histogram", "gRPC call latency by method.");
+    {
+        let latency = stats.rpc_latency.lock().unwrap();
+        for (method, histogram) in latency.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets.iter()) {
+                cumulative += count;
+                exposition.sample(
+                    "robot_admin_rpc_duration_seconds_bucket",
+                    &[("method", method), ("le", &bound.to_string())],
+                    cumulative as f64,
+                );
+            }
+            exposition.sample(
+                "robot_admin_rpc_duration_seconds_bucket",
+                &[("method", method), ("le", "+Inf")],
+                histogram.count as f64,
+            );
+            exposition.sample("robot_admin_rpc_duration_seconds_sum", &[("method", method)], histogram.sum);
+            exposition.sample("robot_admin_rpc_duration_seconds_count", &[("method", method)], histogram.count as f64);
+        }
+    }
+
+    // 只导出白名单中的客户端指标，避免时间序列数量失控
+    exposition.family(
+        "robot_admin_client_metric",
+        "gauge",
+        "Latest numeric value of allowlisted client status metrics.",
+    );
+    let allowlist = &service.config().prometheus_client_metrics;
+    let mut connected: Vec<_> = clients.iter().filter(|(_, client)| client.state.is_connected()).collect();
+    connected.sort_by_key(|(client_id, _)| *client_id);
+    for (client_id, client) in connected {
+        let Some(status) = &client.status else {
+            continue;
+        };
+        for name in allowlist {
+            let Some(value) = status.get(name).and_then(|value| parse_value(value)) else {
+                continue;
+            };
+            exposition.sample(
+                "robot_admin_client_metric",
+                &[
+                    ("client_id", client_id),
+                    ("client_name", &client.name),
+                    ("client_type", &client.client_type),
+                    ("metric", name),
+                ],
+                value,
+            );
+        }
+    }
+
+    exposition.out
+}
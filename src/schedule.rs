@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::selector::Selector;
+
+// 每个定时任务保留的执行记录数量
+const MAX_RUNS: usize = 20;
+
+// 定时任务，到期时把命令发送给目标客户端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub name: String,
+    pub trigger: Trigger,
+    pub target: ScheduleTarget,
+    pub command: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    // 执行超时时间（秒），0 表示使用服务器默认值
+    #[serde(default)]
+    pub timeout_secs: u32,
+    // 暂停的任务到期时不会执行
+    pub enabled: bool,
+    pub created_at: i64,
+    // 创建和最后修改任务的用户或令牌名称
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub updated_by: Option<String>,
+    // 下一次执行的时间（Unix时间戳），None 表示不会再执行
+    pub next_run_at: Option<i64>,
+    // 最近的执行记录，最新的在最后
+    #[serde(default)]
+    pub runs: Vec<ScheduleRun>,
+}
+
+// 执行时间
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    // 在指定时间（Unix时间戳）执行一次
+    RunAt(i64),
+    // 按 cron 表达式（UTC）重复执行
+    Cron(String),
+}
+
+// 定时任务的目标，执行时才解析为具体的客户端
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleTarget {
+    ClientId(String),
+    Selector(String),
+    // 该类型的所有在线客户端
+    ClientType(String),
+}
+
+// 一次执行的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    pub at: i64,
+    // 每个目标客户端的受理结果
+    pub targets: Vec<ScheduleRunTarget>,
+    // 没有发送任何命令的原因，例如没有匹配的客户端或错过了执行时间
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRunTarget {
+    pub client_id: String,
+    pub command_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Trigger {
+    // run_at（RFC 3339 时间）和 cron 必须且只能指定一个
+    pub fn new(run_at: Option<&str>, cron: Option<&str>) -> Result<Self, String> {
+        match (run_at, cron) {
+            (Some(run_at), None) => {
+                let run_at = DateTime::parse_from_rfc3339(run_at)
+                    .map_err(|e| format!("Invalid run_at '{}': {}", run_at, e))?;
+                Ok(Trigger::RunAt(run_at.timestamp()))
+            }
+            (None, Some(cron)) => {
+                parse_cron(cron)?;
+                Ok(Trigger::Cron(cron.trim().to_string()))
+            }
+            _ => Err("Specify exactly one of run_at or cron".to_string()),
+        }
+    }
+
+    // 指定时间之后的下一次执行时间
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        match self {
+            Trigger::RunAt(at) => Some(*at).filter(|at| *at > after),
+            Trigger::Cron(cron) => {
+                let after = Utc.timestamp_opt(after, 0).single()?;
+                parse_cron(cron).ok()?.after(&after).next().map(|at| at.timestamp())
+            }
+        }
+    }
+}
+
+// cron 表达式支持标准的 5 段格式（分 时 日 月 周），也支持带秒的 6 或 7 段格式
+fn parse_cron(cron: &str) -> Result<cron::Schedule, String> {
+    let cron = cron.trim();
+    let expression = if cron.split_whitespace().count() == 5 {
+        format!("0 {}", cron)
+    } else {
+        cron.to_string()
+    };
+    cron::Schedule::from_str(&expression).map_err(|e| format!("Invalid cron expression '{}': {}", cron, e))
+}
+
+impl ScheduleTarget {
+    // client_id、selector 和 client_type 必须且只能指定一个
+    pub fn new(client_id: Option<String>, selector: Option<String>, client_type: Option<String>) -> Result<Self, String> {
+        match (client_id, selector, client_type) {
+            (Some(client_id), None, None) => Ok(ScheduleTarget::ClientId(client_id)),
+            (None, Some(selector), None) => {
+                if selector.parse::<Selector>()?.is_empty() {
+                    return Err("Selector must contain at least one condition".to_string());
+                }
+                Ok(ScheduleTarget::Selector(selector))
+            }
+            (None, None, Some(client_type)) => Ok(ScheduleTarget::ClientType(client_type)),
+            _ => Err("Specify exactly one of client_id, selector or client_type".to_string()),
+        }
+    }
+}
+
+impl Schedule {
+    pub fn new(
+        name: String,
+        trigger: Trigger,
+        target: ScheduleTarget,
+        command: String,
+        parameters: HashMap<String, String>,
+        timeout_secs: u32,
+        enabled: bool,
+    ) -> Result<Self, String> {
+        let now = Utc::now().timestamp();
+        let next_run_at = trigger.next_after(now);
+        if next_run_at.is_none() {
+            return Err(match trigger {
+                Trigger::RunAt(_) => "run_at must be in the future".to_string(),
+                Trigger::Cron(_) => "Cron expression never fires".to_string(),
+            });
+        }
+        Ok(Self {
+            name,
+            trigger,
+            target,
+            command,
+            parameters,
+            timeout_secs,
+            enabled,
+            created_at: now,
+            created_by: None,
+            updated_by: None,
+            next_run_at,
+            runs: Vec::new(),
+        })
+    }
+
+    // 是否已经到了执行时间
+    pub fn is_due(&self, now: i64) -> bool {
+        self.next_run_at.is_some_and(|at| at <= now)
+    }
+
+    // 更新任务定义，保留创建时间、创建者和执行记录，新定义的创建者记为修改者
+    pub fn replace(&mut self, schedule: Schedule) {
+        *self = Self {
+            created_at: self.created_at,
+            created_by: self.created_by.take(),
+            updated_by: schedule.created_by.clone(),
+            runs: std::mem::take(&mut self.runs),
+            ..schedule
+        };
+    }
+
+    // 任务发出的命令记录的发出者，附带最后定义该任务的用户
+    pub fn issuer(&self, schedule_id: &str) -> String {
+        match self.updated_by.as_ref().or(self.created_by.as_ref()) {
+            Some(name) => format!("schedule:{} by {}", schedule_id, name),
+            None => format!("schedule:{}", schedule_id),
+        }
+    }
+
+    // 记录一次执行，并计算下一次执行时间
+    pub fn record_run(&mut self, run: ScheduleRun) {
+        self.next_run_at = self.trigger.next_after(run.at);
+        self.runs.push(run);
+        if self.runs.len() > MAX_RUNS {
+            self.runs.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01T00:00:00Z
+    const NEW_YEAR: i64 = 1_704_067_200;
+
+    #[test]
+    fn run_at_fires_once() {
+        let trigger = Trigger::new(Some("2024-01-01T00:00:00Z"), None).unwrap();
+        assert_eq!(trigger, Trigger::RunAt(NEW_YEAR));
+        assert_eq!(trigger.next_after(NEW_YEAR - 1), Some(NEW_YEAR));
+        assert_eq!(trigger.next_after(NEW_YEAR), None);
+    }
+
+    #[test]
+    fn five_field_cron_runs_on_the_minute() {
+        let trigger = Trigger::new(None, Some(" */15 * * * * ")).unwrap();
+        assert_eq!(trigger, Trigger::Cron("*/15 * * * *".to_string()));
+        assert_eq!(trigger.next_after(NEW_YEAR), Some(NEW_YEAR + 15 * 60));
+        assert_eq!(trigger.next_after(NEW_YEAR + 1), Some(NEW_YEAR + 15 * 60));
+        assert_eq!(trigger.next_after(NEW_YEAR - 1), Some(NEW_YEAR));
+    }
+
+    #[test]
+    fn six_field_cron_supports_seconds() {
+        let trigger = Trigger::new(None, Some("*/10 * * * * *")).unwrap();
+        assert_eq!(trigger.next_after(NEW_YEAR + 3), Some(NEW_YEAR + 10));
+    }
+
+    #[test]
+    fn rejects_invalid_triggers() {
+        assert!(Trigger::new(None, None).is_err());
+        assert!(Trigger::new(Some("2024-01-01T00:00:00Z"), Some("* * * * *")).is_err());
+        assert!(Trigger::new(Some("tomorrow"), None).is_err());
+        assert!(Trigger::new(None, Some("61 * * * *")).is_err());
+    }
+
+    fn schedule(created_by: &str) -> Schedule {
+        let trigger = Trigger::new(None, Some("0 * * * *")).unwrap();
+        let target = ScheduleTarget::ClientType("load_test".to_string());
+        let mut schedule =
+            Schedule::new("hourly".to_string(), trigger, target, "start_test".to_string(), HashMap::new(), 0, true).unwrap();
+        schedule.created_by = Some(created_by.to_string());
+        schedule
+    }
+
+    #[test]
+    fn issuer_names_the_last_editor() {
+        let mut entry = schedule("alice");
+        assert_eq!(entry.issuer("1"), "schedule:1 by alice");
+
+        entry.replace(schedule("bob"));
+        assert_eq!(entry.created_by.as_deref(), Some("alice"));
+        assert_eq!(entry.issuer("1"), "schedule:1 by bob");
+
+        entry.created_by = None;
+        entry.updated_by = None;
+        assert_eq!(entry.issuer("1"), "schedule:1");
+    }
+}
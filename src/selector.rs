@@ -0,0 +1,150 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::grpc::Client;
+
+// 客户端选择器，例如 `type=load_test,region=eu,build!=1.2`
+// 多个条件之间是“与”的关系；键 type、name、version 和 state 匹配客户端本身的字段，
+// 其他键匹配注册时上报的标签。`!=` 条件对没有该标签的客户端也成立
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Requirement {
+    key: String,
+    value: String,
+    negated: bool,
+}
+
+impl Selector {
+    // 只包含一个相等条件的选择器
+    pub fn exact(key: &str, value: &str) -> Self {
+        Self {
+            requirements: vec![Requirement {
+                key: key.to_string(),
+                value: value.to_string(),
+                negated: false,
+            }],
+        }
+    }
+
+    // 是否没有任何条件，空选择器匹配所有客户端
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    pub fn matches(&self, client: &Client) -> bool {
+        self.requirements.iter().all(|requirement| {
+            let actual = match requirement.key.as_str() {
+                "type" => Some(client.client_type.as_str()),
+                "name" => Some(client.name.as_str()),
+                "version" => Some(client.version.as_str()),
+                "state" => Some(client.state.as_str()),
+                key => client.labels.get(key).map(String::as_str),
+            };
+            (actual == Some(requirement.value.as_str())) != requirement.negated
+        })
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let requirements = s
+            .split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(|term| {
+                let (key, value, negated) = match term.split_once("!=") {
+                    Some((key, value)) => (key, value, true),
+                    None => match term.split_once('=') {
+                        Some((key, value)) => (key, value, false),
+                        None => return Err(format!("Invalid selector term '{}', expected key=value or key!=value", term)),
+                    },
+                };
+                let key = key.trim();
+                if key.is_empty() {
+                    return Err(format!("Invalid selector term '{}', missing key", term));
+                }
+                Ok(Requirement {
+                    key: key.to_string(),
+                    value: value.trim().to_string(),
+                    negated,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { requirements })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, requirement) in self.requirements.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            let op = if requirement.negated { "!=" } else { "=" };
+            write!(f, "{}{}{}", requirement.key, op, requirement.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::{Capabilities, ClientState};
+
+    fn client(client_type: &str, labels: &[(&str, &str)]) -> Client {
+        Client {
+            name: "bot-1".to_string(),
+            client_type: client_type.to_string(),
+            version: "1.0".to_string(),
+            instance_key: String::new(),
+            labels: labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            max_players: 0,
+            max_idle_players: None,
+            capabilities: Capabilities::default(),
+            clock: None,
+            secret_hash: None,
+            status: None,
+            state: ClientState::Online,
+            last_seen: 0,
+        }
+    }
+
+    #[test]
+    fn parses_terms_and_trims_whitespace() {
+        let selector: Selector = " type=load_test , region = eu,build!=1.2,".parse().unwrap();
+        assert_eq!(selector.to_string(), "type=load_test,region=eu,build!=1.2");
+        assert!("".parse::<Selector>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_terms() {
+        assert!("type".parse::<Selector>().is_err());
+        assert!("=eu".parse::<Selector>().is_err());
+        assert!("type=a,!=b".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn matches_client_fields_and_labels() {
+        let client = client("load_test", &[("region", "eu")]);
+        assert!("type=load_test,region=eu".parse::<Selector>().unwrap().matches(&client));
+        assert!("state=online,name=bot-1,version=1.0".parse::<Selector>().unwrap().matches(&client));
+        assert!(!"type=load_test,region=us".parse::<Selector>().unwrap().matches(&client));
+        assert!(!"zone=a".parse::<Selector>().unwrap().matches(&client));
+        assert!(Selector::default().matches(&client));
+    }
+
+    #[test]
+    fn negated_terms_match_missing_labels() {
+        let client = client("load_test", &[("build", "1.2")]);
+        assert!(!"build!=1.2".parse::<Selector>().unwrap().matches(&client));
+        assert!("build!=1.3".parse::<Selector>().unwrap().matches(&client));
+        assert!("region!=eu".parse::<Selector>().unwrap().matches(&client));
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, OnceLock};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::grpc::{Batch, Client, Command};
+use crate::schedule::Schedule;
+
+// 持久化的服务状态
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    pub clients: HashMap<String, Client>,
+    pub commands: HashMap<String, Command>,
+    // 每个客户端队列中的命令ID，按执行顺序排列
+    pub queues: HashMap<String, Vec<String>>,
+    pub batches: HashMap<String, Batch>,
+    pub schedules: HashMap<String, Schedule>,
+}
+
+// 存储后端
+// GameControlService 在每次状态变更后写入，启动时通过 load 恢复
+pub trait Storage: Send + Sync {
+    fn load(&self) -> io::Result<Snapshot>;
+    fn put_client(&self, client_id: &str, client: &Client) -> io::Result<()>;
+    fn remove_client(&self, client_id: &str) -> io::Result<()>;
+    fn put_command(&self, command_id: &str, command: &Command) -> io::Result<()>;
+    fn put_queue(&self, client_id: &str, command_ids: &[String]) -> io::Result<()>;
+    fn put_batch(&self, batch_id: &str, batch: &Batch) -> io::Result<()>;
+    fn put_schedule(&self, schedule_id: &str, schedule: &Schedule) -> io::Result<()>;
+    fn remove_schedule(&self, schedule_id: &str) -> io::Result<()>;
+}
+
+// 不做持久化，未配置数据目录时使用
+pub struct MemoryStorage;
+
+impl Storage for MemoryStorage {
+    fn load(&self) -> io::Result<Snapshot> {
+        Ok(Snapshot::default())
+    }
+
+    fn put_client(&self, _client_id: &str, _client: &Client) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn remove_client(&self, _client_id: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn put_command(&self, _command_id: &str, _command: &Command) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn put_queue(&self, _client_id: &str, _command_ids: &[String]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn put_batch(&self, _batch_id: &str, _batch: &Batch) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn put_schedule(&self, _schedule_id: &str, _schedule: &Schedule) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn remove_schedule(&self, _schedule_id: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 日志中的一条记录，同一对象的后一条记录覆盖前一条
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Client { id: String, client: Client },
+    ClientRemoved { id: String },
+    Command { id: String, command: Command },
+    Queue { client_id: String, command_ids: Vec<String> },
+    Batch { id: String, batch: Batch },
+    Schedule { id: String, schedule: Schedule },
+    ScheduleRemoved { id: String },
+}
+
+// 日志超过该大小（字节）且超过上次压缩后大小的两倍时重新压缩
+const COMPACT_MIN_BYTES: u64 = 16 * 1024 * 1024;
+
+impl Snapshot {
+    // 重放一条日志记录
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Client { id, client } => {
+                self.clients.insert(id, client);
+            }
+            Record::ClientRemoved { id } => {
+                self.clients.remove(&id);
+                self.queues.remove(&id);
+            }
+            Record::Command { id, command } => {
+                self.commands.insert(id, command);
+            }
+            Record::Queue { client_id, command_ids } => {
+                if command_ids.is_empty() {
+                    self.queues.remove(&client_id);
+                } else {
+                    self.queues.insert(client_id, command_ids);
+                }
+            }
+            Record::Batch { id, batch } => {
+                self.batches.insert(id, batch);
+            }
+            Record::Schedule { id, schedule } => {
+                self.schedules.insert(id, schedule);
+            }
+            Record::ScheduleRemoved { id } => {
+                self.schedules.remove(&id);
+            }
+        }
+    }
+
+    // 每个对象一条记录
+    fn records(&self) -> impl Iterator<Item = Record> + '_ {
+        self.clients
+            .iter()
+            .map(|(id, client)| Record::Client { id: id.clone(), client: client.clone() })
+            .chain(self.commands.iter().map(|(id, command)| Record::Command {
+                id: id.clone(),
+                command: command.clone(),
+            }))
+            .chain(self.queues.iter().map(|(client_id, command_ids)| Record::Queue {
+                client_id: client_id.clone(),
+                command_ids: command_ids.clone(),
+            }))
+            .chain(self.batches.iter().map(|(id, batch)| Record::Batch {
+                id: id.clone(),
+                batch: batch.clone(),
+            }))
+            .chain(self.schedules.iter().map(|(id, schedule)| Record::Schedule {
+                id: id.clone(),
+                schedule: schedule.clone(),
+            }))
+    }
+}
+
+// 追加写入的日志文件，每行一条 JSON 记录
+// 加载时重放日志并压缩为每个对象一条记录，之后由后台线程写入，调用方不会阻塞在文件 I/O 上
+pub struct FileStorage {
+    path: PathBuf,
+    // 加载完成后启动的写入线程
+    sender: OnceLock<mpsc::Sender<Record>>,
+    writer: OnceLock<thread::JoinHandle<()>>,
+}
+
+impl FileStorage {
+    const JOURNAL_FILE: &'static str = "journal.jsonl";
+
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(Self::JOURNAL_FILE);
+        Self::open_append(&path)?;
+        Ok(Self { path, sender: OnceLock::new(), writer: OnceLock::new() })
+    }
+
+    fn open_append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn append(&self, record: Record) -> io::Result<()> {
+        let sender = self
+            .sender
+            .get()
+            .ok_or_else(|| io::Error::other("Journal is not loaded"))?;
+        sender
+            .send(record)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Journal writer stopped"))
+    }
+}
+
+// 写入线程持有的日志状态
+struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    // 与日志内容一致的状态，用于压缩时重写日志
+    snapshot: Snapshot,
+    // 当前日志大小和上次压缩后的大小（字节）
+    bytes: u64,
+    compacted_bytes: u64,
+}
+
+impl Journal {
+    // 用压缩后的状态替换日志文件
+    fn compact(path: &Path, snapshot: &Snapshot) -> io::Result<(BufWriter<File>, u64)> {
+        let tmp_path = path.with_extension("jsonl.tmp");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        let mut bytes = 0;
+        for record in snapshot.records() {
+            let line = serde_json::to_vec(&record)?;
+            tmp.write_all(&line)?;
+            tmp.write_all(b"\n")?;
+            bytes += line.len() as u64 + 1;
+        }
+        tmp.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        fs::rename(&tmp_path, path)?;
+        Ok((BufWriter::new(FileStorage::open_append(path)?), bytes))
+    }
+
+    fn write(&mut self, record: Record) -> io::Result<()> {
+        let line = serde_json::to_vec(&record)?;
+        self.writer.write_all(&line)?;
+        self.writer.write_all(b"\n")?;
+        self.bytes += line.len() as u64 + 1;
+        self.snapshot.apply(record);
+        Ok(())
+    }
+
+    fn compact_if_needed(&mut self) -> io::Result<()> {
+        if self.bytes < COMPACT_MIN_BYTES.max(self.compacted_bytes * 2) {
+            return Ok(());
+        }
+        self.writer.flush()?;
+        let (writer, bytes) = Self::compact(&self.path, &self.snapshot)?;
+        println!("Compacted journal from {} to {} bytes", self.bytes, bytes);
+        self.writer = writer;
+        self.bytes = bytes;
+        self.compacted_bytes = bytes;
+        Ok(())
+    }
+
+    // 一次写入通道中积压的所有记录后再刷新，通道关闭时退出
+    fn run(mut self, receiver: mpsc::Receiver<Record>) {
+        while let Ok(record) = receiver.recv() {
+            let result = self
+                .write(record)
+                .and_then(|_| receiver.try_iter().try_for_each(|record| self.write(record)))
+                .and_then(|_| self.writer.flush())
+                .and_then(|_| self.compact_if_needed());
+            if let Err(e) = result {
+                println!("Failed to write journal: {}", e);
+            }
+        }
+    }
+}
+
+// 关闭通道并等待写入线程写完积压的记录
+impl Drop for FileStorage {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self) -> io::Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+        let reader = BufReader::new(File::open(&self.path)?);
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // 进程在写入过程中退出时，最后一行可能不完整
+            match serde_json::from_str(&line) {
+                Ok(record) => snapshot.apply(record),
+                Err(e) => println!("Skipping corrupt journal record at line {}: {}", index + 1, e),
+            }
+        }
+
+        let (writer, bytes) = Journal::compact(&self.path, &snapshot)?;
+        let journal = Journal {
+            path: self.path.clone(),
+            writer,
+            snapshot: snapshot.clone(),
+            bytes,
+            compacted_bytes: bytes,
+        };
+        if self.sender.get().is_some() {
+            return Err(io::Error::other("Journal is already loaded"));
+        }
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("journal-writer".to_string())
+            .spawn(move || journal.run(receiver))?;
+        let _ = self.sender.set(sender);
+        let _ = self.writer.set(writer);
+        Ok(snapshot)
+    }
+
+    fn put_client(&self, client_id: &str, client: &Client) -> io::Result<()> {
+        self.append(Record::Client { id: client_id.to_string(), client: client.clone() })
+    }
+
+    fn remove_client(&self, client_id: &str) -> io::Result<()> {
+        self.append(Record::ClientRemoved { id: client_id.to_string() })
+    }
+
+    fn put_command(&self, command_id: &str, command: &Command) -> io::Result<()> {
+        self.append(Record::Command { id: command_id.to_string(), command: command.clone() })
+    }
+
+    fn put_queue(&self, client_id: &str, command_ids: &[String]) -> io::Result<()> {
+        self.append(Record::Queue {
+            client_id: client_id.to_string(),
+            command_ids: command_ids.to_vec(),
+        })
+    }
+
+    fn put_batch(&self, batch_id: &str, batch: &Batch) -> io::Result<()> {
+        self.append(Record::Batch { id: batch_id.to_string(), batch: batch.clone() })
+    }
+
+    fn put_schedule(&self, schedule_id: &str, schedule: &Schedule) -> io::Result<()> {
+        self.append(Record::Schedule { id: schedule_id.to_string(), schedule: schedule.clone() })
+    }
+
+    fn remove_schedule(&self, schedule_id: &str) -> io::Result<()> {
+        self.append(Record::ScheduleRemoved { id: schedule_id.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    // 测试结束时删除的临时数据目录
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("robot_admin_storage_{}", Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn client(name: &str) -> Client {
+        serde_json::from_value(json!({
+            "name": name,
+            "client_type": "load_test",
+            "version": "1.0",
+            "status": null,
+            "last_seen": 0,
+        }))
+        .unwrap()
+    }
+
+    fn queue(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn journal_lines(dir: &TempDir) -> usize {
+        fs::read_to_string(dir.0.join(FileStorage::JOURNAL_FILE)).unwrap().lines().count()
+    }
+
+    fn load(dir: &TempDir) -> (FileStorage, Snapshot) {
+        let storage = FileStorage::open(&dir.0).unwrap();
+        let snapshot = storage.load().unwrap();
+        (storage, snapshot)
+    }
+
+    #[test]
+    fn round_trip_and_compaction() {
+        let dir = TempDir::new();
+        let (storage, snapshot) = load(&dir);
+        assert!(snapshot.clients.is_empty());
+        storage.put_client("a", &client("first")).unwrap();
+        storage.put_client("a", &client("renamed")).unwrap();
+        storage.put_client("b", &client("removed")).unwrap();
+        storage.put_queue("a", &queue(&["1", "2"])).unwrap();
+        storage.put_queue("b", &queue(&["3"])).unwrap();
+        storage.remove_client("b").unwrap();
+        // 释放存储时等待写入线程写完
+        drop(storage);
+        assert_eq!(journal_lines(&dir), 6);
+
+        // 重新加载时重放日志并压缩为每个对象一条记录
+        let (storage, snapshot) = load(&dir);
+        assert_eq!(snapshot.clients.len(), 1);
+        assert_eq!(snapshot.clients["a"].name, "renamed");
+        assert_eq!(snapshot.queues, HashMap::from([("a".to_string(), queue(&["1", "2"]))]));
+        assert_eq!(journal_lines(&dir), 2);
+        drop(storage);
+
+        // 日志超过压缩阈值后重写
+        let path = dir.0.join(FileStorage::JOURNAL_FILE);
+        let (writer, bytes) = Journal::compact(&path, &snapshot).unwrap();
+        let mut journal = Journal { path, writer, snapshot, bytes, compacted_bytes: bytes };
+        for index in 0..10 {
+            let command_ids = queue(&[&index.to_string()]);
+            journal.write(Record::Queue { client_id: "a".to_string(), command_ids }).unwrap();
+        }
+        journal.compact_if_needed().unwrap();
+        journal.writer.flush().unwrap();
+        assert_eq!(journal_lines(&dir), 12);
+        journal.bytes = COMPACT_MIN_BYTES;
+        journal.compact_if_needed().unwrap();
+        assert_eq!(journal_lines(&dir), 2);
+        assert!(journal.bytes < COMPACT_MIN_BYTES);
+        assert_eq!(journal.compacted_bytes, journal.bytes);
+        drop(journal);
+
+        let (_storage, snapshot) = load(&dir);
+        assert_eq!(snapshot.queues["a"], queue(&["9"]));
+        assert_eq!(snapshot.clients["a"].name, "renamed");
+    }
+
+    #[test]
+    fn skips_truncated_last_record() {
+        let dir = TempDir::new();
+        let (storage, _) = load(&dir);
+        storage.put_client("a", &client("first")).unwrap();
+        storage.put_queue("a", &queue(&["1"])).unwrap();
+        drop(storage);
+
+        // 模拟写入过程中退出，最后一行不完整
+        let path = dir.0.join(FileStorage::JOURNAL_FILE);
+        let mut file = FileStorage::open_append(&path).unwrap();
+        file.write_all(br#"{"type":"queue","client_id":"a","command_ids":["#).unwrap();
+        drop(file);
+
+        let (storage, snapshot) = load(&dir);
+        assert_eq!(snapshot.clients["a"].name, "first");
+        assert_eq!(snapshot.queues["a"], queue(&["1"]));
+        // 加载时的压缩去掉了损坏的记录，之后的写入从新的一行开始
+        assert_eq!(journal_lines(&dir), 2);
+        storage.put_queue("a", &queue(&["2"])).unwrap();
+        drop(storage);
+
+        let (_storage, snapshot) = load(&dir);
+        assert_eq!(snapshot.queues["a"], queue(&["2"]));
+    }
+}
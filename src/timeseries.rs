@@ -0,0 +1,319 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+
+// 原始数据点超过保留时间后只保留按分钟聚合的结果
+pub const BUCKET_SECS: i64 = 60;
+// 每个客户端最多记录的指标数量，超出的新指标被忽略
+const MAX_SERIES: usize = 64;
+// 一次查询最多返回的点数，足够按秒查询一小时的原始数据
+const MAX_POINTS: i64 = 3600;
+
+// 不作为数值指标记录的状态字段
+fn is_tracked(name: &str) -> bool {
+    !name.starts_with("current_command")
+        && !name.starts_with("parameter_")
+        && name != "command_started_at"
+        && name != "completed_command_id"
+}
+
+// 解析数值指标，允许带 "%"、"MB" 之类的单位后缀
+pub fn parse_value(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_alphabetic() || c == '%')
+        .trim_end()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+// 数据保留时间（秒）
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    // 原始数据点的保留时间
+    pub raw_secs: i64,
+    // 分钟聚合数据的保留时间
+    pub total_secs: i64,
+}
+
+// 查询结果中的一个点，聚合了 [timestamp, timestamp + step) 内的数据
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MetricPoint {
+    pub timestamp: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    start: i64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    count: u32,
+}
+
+impl Bucket {
+    fn new(start: i64, value: f64) -> Self {
+        Self { start, sum: value, min: value, max: value, count: 1 }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.merge(&Bucket::new(self.start, value));
+    }
+
+    fn merge(&mut self, other: &Bucket) {
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+    }
+
+    fn point(&self) -> MetricPoint {
+        MetricPoint {
+            timestamp: self.start,
+            avg: self.sum / f64::from(self.count),
+            min: self.min,
+            max: self.max,
+            count: self.count,
+        }
+    }
+}
+
+// 一个指标的数据：最近的原始数据点和更长时间的分钟聚合
+#[derive(Debug, Default)]
+struct Series {
+    raw: VecDeque<(i64, f64)>,
+    buckets: VecDeque<Bucket>,
+}
+
+impl Series {
+    fn push(&mut self, timestamp: i64, value: f64) {
+        self.raw.push_back((timestamp, value));
+        let start = timestamp - timestamp.rem_euclid(BUCKET_SECS);
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.start == start => bucket.add(value),
+            _ => self.buckets.push_back(Bucket::new(start, value)),
+        }
+    }
+
+    fn prune(&mut self, now: i64, retention: Retention) {
+        while self.raw.front().is_some_and(|(timestamp, _)| *timestamp < now - retention.raw_secs) {
+            self.raw.pop_front();
+        }
+        while self.buckets.front().is_some_and(|bucket| bucket.start + BUCKET_SECS <= now - retention.total_secs) {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+// 一个客户端的所有数值指标
+#[derive(Debug, Default)]
+pub struct ClientMetrics {
+    series: HashMap<String, Series>,
+}
+
+// 查询参数校验后的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSeries {
+    pub name: String,
+    pub from: i64,
+    pub to: i64,
+    pub step: i64,
+    // 是否由分钟聚合数据计算，此时 step 是 60 秒的整数倍
+    pub downsampled: bool,
+    pub points: Vec<MetricPoint>,
+}
+
+impl ClientMetrics {
+    // 记录一次状态上报中能解析为数值的指标
+    pub fn record(&mut self, metrics: &HashMap<String, String>, now: i64, retention: Retention) {
+        for (name, value) in metrics.iter().filter(|(name, _)| is_tracked(name)) {
+            let Some(value) = parse_value(value) else {
+                continue;
+            };
+            if !self.series.contains_key(name) && self.series.len() >= MAX_SERIES {
+                continue;
+            }
+            let series = self.series.entry(name.clone()).or_default();
+            series.push(now, value);
+            series.prune(now, retention);
+        }
+    }
+
+    // 丢弃超过保留时间的数据，返回是否已经没有数据
+    pub fn prune(&mut self, now: i64, retention: Retention) -> bool {
+        for series in self.series.values_mut() {
+            series.prune(now, retention);
+        }
+        self.series.retain(|_, series| !series.is_empty());
+        self.series.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.series.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // 按 step（秒）聚合 [from, to] 内的数据，step 为 0 时自动选择
+    // 查询范围都在原始数据保留时间内时使用原始数据，否则使用分钟聚合数据，此时返回的 from 向下取整到 step 的整数倍
+    pub fn query(&self, name: &str, from: i64, to: i64, step: i64, now: i64, retention: Retention) -> Result<MetricSeries, String> {
+        let series = self.series.get(name).ok_or_else(|| format!("No data for metric '{}'", name))?;
+        if from > to {
+            return Err("from must not be after to".to_string());
+        }
+        if step < 0 {
+            return Err("step must not be negative".to_string());
+        }
+        // 参数来自查询字符串，极端值会使计算溢出
+        let invalid_range = || "Invalid range".to_string();
+        let downsampled = from < now - retention.raw_secs;
+        let span = to.checked_sub(from).and_then(|span| span.checked_add(MAX_POINTS - 1)).ok_or_else(invalid_range)?;
+        let min_step = (span / MAX_POINTS).max(1);
+        let mut step = if step == 0 { min_step } else { step };
+        if downsampled {
+            // 向上取整为整分钟
+            step = step.checked_add(BUCKET_SECS - 1).ok_or_else(invalid_range)? / BUCKET_SECS * BUCKET_SECS;
+        }
+        if step < min_step {
+            return Err(format!("Too many points, use a step of at least {} seconds", min_step));
+        }
+        // 分钟聚合数据按 step 的整数倍对齐（也是整分钟），包含与查询范围重叠的第一个桶，
+        // 同样的数据不会因为查询起点不同而得到不同的结果
+        let from = if downsampled { from.checked_sub(from.rem_euclid(step)).ok_or_else(invalid_range)? } else { from };
+
+        let buckets: Vec<Bucket> = if downsampled {
+            series.buckets.iter().filter(|bucket| bucket.start >= from && bucket.start <= to).copied().collect()
+        } else {
+            series
+                .raw
+                .iter()
+                .filter(|(timestamp, _)| *timestamp >= from && *timestamp <= to)
+                .map(|(timestamp, value)| Bucket::new(*timestamp, *value))
+                .collect()
+        };
+
+        let mut points: Vec<Bucket> = Vec::new();
+        for bucket in buckets {
+            let start = from + (bucket.start - from) / step * step;
+            match points.last_mut() {
+                Some(point) if point.start == start => point.merge(&bucket),
+                _ => points.push(Bucket { start, ..bucket }),
+            }
+        }
+
+        Ok(MetricSeries {
+            name: name.to_string(),
+            from,
+            to,
+            step,
+            downsampled,
+            points: points.iter().map(Bucket::point).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RETENTION: Retention = Retention { raw_secs: 600, total_secs: 86_400 };
+    // 整小时，便于检查分钟对齐
+    const START: i64 = 1_704_067_200;
+
+    fn record(metrics: &mut ClientMetrics, at: i64, value: &str) {
+        let status = HashMap::from([("cpu".to_string(), value.to_string())]);
+        metrics.record(&status, at, RETENTION);
+    }
+
+    #[test]
+    fn parses_values_with_units() {
+        assert_eq!(parse_value("42.5%"), Some(42.5));
+        assert_eq!(parse_value(" 512 MB"), Some(512.0));
+        assert_eq!(parse_value("NaN"), None);
+        assert_eq!(parse_value("idle"), None);
+    }
+
+    #[test]
+    fn raw_query_groups_points_by_step() {
+        let mut metrics = ClientMetrics::default();
+        for (offset, value) in [(0, "1"), (5, "3"), (10, "10"), (25, "20")] {
+            record(&mut metrics, START + offset, value);
+        }
+        let now = START + 30;
+        let series = metrics.query("cpu", START, now, 10, now, RETENTION).unwrap();
+        assert!(!series.downsampled);
+        let points: Vec<_> = series.points.iter().map(|p| (p.timestamp, p.avg, p.min, p.max, p.count)).collect();
+        assert_eq!(
+            points,
+            vec![(START, 2.0, 1.0, 3.0, 2), (START + 10, 10.0, 10.0, 10.0, 1), (START + 20, 20.0, 20.0, 20.0, 1)]
+        );
+    }
+
+    #[test]
+    fn downsampled_query_aligns_to_buckets() {
+        let mut metrics = ClientMetrics::default();
+        for minute in 0..10 {
+            record(&mut metrics, START + minute * 60 + 30, &minute.to_string());
+        }
+        let now = START + 3600;
+
+        // 步长向上取整为整分钟，起点向下取整到步长的整数倍，包含重叠的第一个桶
+        let series = metrics.query("cpu", START + 70, START + 599, 100, now, RETENTION).unwrap();
+        assert!(series.downsampled);
+        assert_eq!(series.step, 120);
+        assert_eq!(series.from, START);
+        let points: Vec<_> = series.points.iter().map(|p| (p.timestamp, p.count)).collect();
+        assert_eq!(points, vec![(START, 2), (START + 120, 2), (START + 240, 2), (START + 360, 2), (START + 480, 2)]);
+
+        // 不同的查询起点得到相同的点
+        let shifted = metrics.query("cpu", START + 10, START + 599, 120, now, RETENTION).unwrap();
+        let shifted: Vec<_> = shifted.points.iter().map(|p| (p.timestamp, p.count)).collect();
+        assert_eq!(shifted, points);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        let mut metrics = ClientMetrics::default();
+        record(&mut metrics, START, "1");
+        assert!(metrics.query("memory", START, START, 0, START, RETENTION).is_err());
+        assert!(metrics.query("cpu", START + 1, START, 0, START, RETENTION).is_err());
+        assert!(metrics.query("cpu", START, START, -1, START, RETENTION).is_err());
+        assert!(metrics.query("cpu", START - 61 * 3600, START, 60, START, RETENTION).is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_ranges() {
+        let mut metrics = ClientMetrics::default();
+        record(&mut metrics, START, "1");
+        for (from, to, step) in [
+            (i64::MIN, i64::MAX, 0),
+            (i64::MIN, START, 0),
+            (START - 7200, START, i64::MAX),
+            (i64::MIN + 1, i64::MIN + 2, 120),
+        ] {
+            let result = metrics.query("cpu", from, to, step, START, RETENTION);
+            assert_eq!(result.unwrap_err(), "Invalid range", "{} {} {}", from, to, step);
+        }
+    }
+
+    #[test]
+    fn ignores_command_fields() {
+        let mut metrics = ClientMetrics::default();
+        let status = HashMap::from([
+            ("cpu".to_string(), "1".to_string()),
+            ("completed_command_id".to_string(), "7".to_string()),
+            ("parameter_players".to_string(), "10".to_string()),
+        ]);
+        metrics.record(&status, START, RETENTION);
+        assert_eq!(metrics.names(), vec!["cpu".to_string()]);
+    }
+}
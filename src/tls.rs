@@ -0,0 +1,56 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use axum_server::tls_rustls::RustlsConfig;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tonic::Request;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config::Config;
+
+// 服务器证书和私钥必须同时配置
+fn server_identity(config: &Config) -> io::Result<Option<(&Path, &Path)>> {
+    match (&config.tls_cert_path, &config.tls_key_path, &config.grpc_client_ca_path) {
+        (Some(cert), Some(key), _) => Ok(Some((cert, key))),
+        (None, None, None) => Ok(None),
+        (None, None, Some(_)) => Err(invalid("ROBOT_ADMIN_GRPC_CLIENT_CA requires ROBOT_ADMIN_TLS_CERT and ROBOT_ADMIN_TLS_KEY")),
+        _ => Err(invalid("ROBOT_ADMIN_TLS_CERT and ROBOT_ADMIN_TLS_KEY must be set together")),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn read(path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+// gRPC 服务的 TLS 配置，设置了客户端 CA 时要求客户端证书（mTLS）
+pub fn grpc_config(config: &Config) -> io::Result<Option<ServerTlsConfig>> {
+    let Some((cert, key)) = server_identity(config)? else {
+        return Ok(None);
+    };
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(read(cert)?, read(key)?));
+    if let Some(ca) = &config.grpc_client_ca_path {
+        tls = tls.client_ca_root(Certificate::from_pem(read(ca)?));
+    }
+    Ok(Some(tls))
+}
+
+// 网页服务的 TLS 配置，与 gRPC 使用同一份证书
+pub async fn web_config(config: &Config) -> io::Result<Option<RustlsConfig>> {
+    let Some((cert, key)) = server_identity(config)? else {
+        return Ok(None);
+    };
+    RustlsConfig::from_pem(read(cert)?, read(key)?).await.map(Some)
+}
+
+// mTLS 连接中客户端证书的 CN，未启用 mTLS 时为 None
+pub fn peer_common_name<T>(request: &Request<T>) -> Option<String> {
+    let certs = request.peer_certs()?;
+    let (_, cert) = X509Certificate::from_der(certs.first()?.get_ref()).ok()?;
+    let common_name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    Some(common_name.to_string())
+}
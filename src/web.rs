@@ -1,23 +1,35 @@
 use axum::{
-    extract::State,
-    response::IntoResponse,
-    routing::{get, post},
-    Router, Json,
+    extract::{MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post},
+    Extension, Router, Json,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::convert::Infallible;
 use std::sync::Arc;
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::services::ServeDir;
 use uuid::Uuid;
 use chrono::Utc;
 
 use crate::grpc::game_control::PendingCommand;
-use crate::grpc::GameControlService;
-
-#[derive(Debug, Serialize)]
-struct ClientListResponse {
-    clients: Vec<ClientInfo>,
-}
+use crate::auth::{Principal, Role, SESSION_TTL_SECS};
+use crate::events::EventFilter;
+use crate::prometheus;
+use crate::schedule::{Schedule, ScheduleTarget, Trigger};
+use crate::selector::Selector;
+use crate::workflow::{Workflow, WorkflowDefinition};
+use crate::grpc::{
+    Barrier, BarrierReport, Batch, BatchCounts, BatchTargets, CancelOutcome, Capabilities, ClientState, ClockSync, Command, CommandEvent,
+    CommandOutcome, CommandPlacement, CommandStatus, GameControlService,
+};
 
 #[derive(Debug, Serialize)]
 struct ClientInfo {
@@ -25,9 +37,21 @@ struct ClientInfo {
     name: String,
     client_type: String,
     version: String,
+    instance_key: String,
+    labels: std::collections::HashMap<String, String>,
+    max_players: u32,
+    max_idle_players: Option<u32>,
+    // 还能分配的玩家数
+    free_capacity: u32,
+    state: ClientState,
+    // 注册时声明的协议版本和支持的命令
+    capabilities: Capabilities,
+    // 最近一次时间同步测量的时钟偏差和往返时间
+    clock: Option<ClockSync>,
     last_seen: i64,
     metrics: std::collections::HashMap<String, String>,
     current_command: Option<CurrentCommand>,
+    queue_length: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -37,27 +61,471 @@ struct CurrentCommand {
     started_at: i64,
 }
 
+#[derive(Debug, Serialize)]
+struct CommandInfo {
+    id: String,
+    client_id: String,
+    command: String,
+    parameters: std::collections::HashMap<String, String>,
+    status: CommandStatus,
+    created_at: i64,
+    started_at: Option<i64>,
+    finished_at: Option<i64>,
+    timeout_secs: u32,
+    result: Option<CommandOutcome>,
+    cancelling: bool,
+    cancel_reason: Option<String>,
+    events: Vec<CommandEvent>,
+    batch_id: Option<String>,
+    issued_by: Option<String>,
+}
+
+impl CommandInfo {
+    fn new(id: String, command: Command) -> Self {
+        let cancelling = command.is_cancelling();
+        Self {
+            id,
+            client_id: command.client_id,
+            command: command.command,
+            parameters: command.parameters,
+            status: command.status,
+            created_at: command.created_at,
+            started_at: command.started_at,
+            finished_at: command.finished_at,
+            timeout_secs: command.timeout_secs,
+            cancelling,
+            result: command.outcome,
+            cancel_reason: command.cancel_reason,
+            events: command.events,
+            batch_id: command.batch_id,
+            issued_by: command.issued_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchInfo {
+    id: String,
+    command: String,
+    parameters: std::collections::HashMap<String, String>,
+    selector: Option<String>,
+    total_players: Option<u32>,
+    created_at: i64,
+    counts: BatchCounts,
+    // 屏障模式下的开始时间和实际开始时间的偏差
+    barrier: Option<BarrierReport>,
+    targets: Vec<BatchTargetInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchTargetInfo {
+    client_id: String,
+    command_id: Option<String>,
+    // 子命令的当前状态，未受理时为空
+    status: Option<CommandStatus>,
+    error: Option<String>,
+    player_count: Option<u32>,
+    player_offset: Option<u32>,
+    acked_at_ms: Option<i64>,
+    started_at_ms: Option<i64>,
+}
+
+impl BatchInfo {
+    fn new(id: String, batch: Batch, statuses: std::collections::HashMap<String, CommandStatus>) -> Self {
+        let counts = BatchCounts::new(&batch, &statuses);
+        let barrier = batch.barrier_report();
+        let targets = batch.targets.into_iter().map(|target| BatchTargetInfo {
+            status: target.command_id.as_ref().and_then(|id| statuses.get(id).cloned()),
+            client_id: target.client_id,
+            command_id: target.command_id,
+            error: target.error,
+            player_count: target.player_count,
+            player_offset: target.player_offset,
+            acked_at_ms: target.acked_at_ms,
+            started_at_ms: target.started_at_ms,
+        }).collect();
+        Self {
+            id,
+            command: batch.command,
+            parameters: batch.parameters,
+            selector: batch.selector,
+            total_players: batch.total_players,
+            created_at: batch.created_at,
+            counts,
+            barrier,
+            targets,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct SendCommandRequest {
-    client_id: String,
+    // 目标客户端，与 selector 二选一
+    client_id: Option<String>,
+    // 客户端选择器，命令发送给所有匹配的在线客户端
+    selector: Option<String>,
     command: String,
     pub parameters: Option<std::collections::HashMap<String, String>>,
+    // 执行超时时间（秒），不指定时使用服务器默认值
+    timeout_secs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendBatchRequest {
+    // 目标客户端ID列表，与 selector 二选一
+    client_ids: Option<Vec<String>>,
+    selector: Option<String>,
+    command: String,
+    parameters: Option<std::collections::HashMap<String, String>>,
+    timeout_secs: Option<u32>,
+    // 按空闲容量分配给目标客户端的玩家总数
+    total_players: Option<u32>,
+    // 屏障模式：所有目标确认后以统一的时间开始执行
+    barrier: Option<bool>,
+    // 屏障模式下全部确认后到开始执行的间隔（毫秒）和等待确认的最长时间（秒）
+    start_delay_ms: Option<u32>,
+    barrier_timeout_secs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequest {
+    name: Option<String>,
+    // 执行时间，run_at（RFC 3339，例如 2024-05-01T02:00:00Z）与 cron 二选一
+    run_at: Option<String>,
+    cron: Option<String>,
+    // 目标，client_id、selector 和 client_type 三选一
+    client_id: Option<String>,
+    selector: Option<String>,
+    client_type: Option<String>,
+    command: String,
+    parameters: Option<std::collections::HashMap<String, String>>,
+    timeout_secs: Option<u32>,
+    // 默认启用
+    enabled: Option<bool>,
+}
+
+impl ScheduleRequest {
+    fn into_schedule(self, principal: &Principal) -> Result<Schedule, String> {
+        let trigger = Trigger::new(self.run_at.as_deref(), self.cron.as_deref())?;
+        let target = ScheduleTarget::new(self.client_id, self.selector, self.client_type)?;
+        let mut schedule = Schedule::new(
+            self.name.unwrap_or_else(|| self.command.clone()),
+            trigger,
+            target,
+            self.command,
+            self.parameters.unwrap_or_default(),
+            self.timeout_secs.unwrap_or(0),
+            self.enabled.unwrap_or(true),
+        )?;
+        schedule.created_by = Some(principal.name.clone());
+        Ok(schedule)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ScheduleInfo {
+    id: String,
+    #[serde(flatten)]
+    schedule: Schedule,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkflowInfo {
+    id: String,
+    #[serde(flatten)]
+    workflow: Workflow,
+}
+
+#[derive(Debug, Serialize)]
+struct QueuedCommand {
+    command_id: String,
+    command: String,
+    parameters: std::collections::HashMap<String, String>,
+    created_at: i64,
+}
+
+impl From<PendingCommand> for QueuedCommand {
+    fn from(command: PendingCommand) -> Self {
+        Self {
+            command_id: command.command_id,
+            command: command.command,
+            parameters: command.parameters,
+            created_at: command.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EmergencyStopRequest {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientListQuery {
+    // 只返回指定状态的客户端，未指定时返回全部
+    state: Option<ClientState>,
+    // 客户端选择器，例如 type=load_test,region=eu,build!=1.2
+    selector: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CatalogQuery {
+    // 只返回该客户端类型支持的命令
+    client_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelCommandQuery {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReorderQueueRequest {
+    command_ids: Vec<String>,
 }
 
 pub fn router(service: Arc<GameControlService>) -> Router {
     Router::new()
+        .route("/metrics", get(prometheus_metrics))
+        .route("/api/events", get(stream_events))
         .route("/api/clients", get(list_clients))
+        .route("/api/clients/:id", delete(remove_client))
         .route("/api/commands", post(send_command))
+        .route("/api/commands/:id", get(get_command).delete(cancel_command))
+        .route("/api/catalog", get(get_catalog))
+        .route("/api/batches", post(send_batch))
+        .route("/api/batches/:id", get(get_batch))
+        .route("/api/schedules", get(list_schedules).post(create_schedule))
+        .route("/api/schedules/:id", get(get_schedule).put(update_schedule).delete(remove_schedule))
+        .route("/api/workflows", get(list_workflows).post(start_workflow))
+        .route("/api/workflows/:id", get(get_workflow).delete(cancel_workflow))
+        .route("/api/emergency-stop", post(engage_emergency_stop).delete(clear_emergency_stop))
+        .route("/api/clients/:id/metrics", get(get_client_metrics))
+        .route("/api/clients/:id/queue", get(list_queue).put(reorder_queue))
+        .route("/api/clients/:id/queue/:command_id", delete(remove_queued))
+        .route("/api/me", get(current_user))
+        // 以上接口都需要认证，登录和登出除外
+        .route_layer(middleware::from_fn_with_state(service.clone(), authorize))
+        .route("/api/login", post(login))
+        .route("/api/logout", post(logout))
         .nest_service("/static", ServeDir::new("static"))
         .fallback_service(ServeDir::new("static"))
         .with_state(service)
 }
 
+// 登录后通过 Cookie 携带会话令牌，EventSource 无法设置请求头
+const SESSION_COOKIE: &str = "robot_admin_session";
+
+// 访问接口需要的最低角色：查询只需要 viewer，其他修改操作需要 operator，删除客户端和解除紧急停止需要 admin
+fn required_role(method: &Method, path: &str) -> Role {
+    match (method, path) {
+        (&Method::DELETE, "/api/clients/:id") | (&Method::DELETE, "/api/emergency-stop") => Role::Admin,
+        (&Method::GET | &Method::HEAD, _) => Role::Viewer,
+        _ => Role::Operator,
+    }
+}
+
+// 请求携带的令牌：Authorization: Bearer <token> 或会话 Cookie
+fn request_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(token.trim().to_string());
+    }
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(SESSION_COOKIE)?.strip_prefix('='))
+        .map(str::to_string)
+}
+
+// 认证并检查角色，通过后把身份放入请求扩展，供处理函数记录命令的发出者
+async fn authorize(
+    State(service): State<Arc<GameControlService>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let principal = match service.auth() {
+        None => Principal::anonymous(),
+        Some(auth) => match request_token(request.headers()).and_then(|token| auth.authenticate(&token)) {
+            Some(principal) => principal,
+            None => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(json!({
+                        "success": false,
+                        "error": "Authentication required",
+                    })),
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let required = required_role(request.method(), &path);
+    if principal.role < required {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "error": format!("Requires the {} role", required.as_str()),
+            })),
+        )
+            .into_response();
+    }
+
+    request.extensions_mut().insert(principal);
+    next.run(request).await
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+async fn login(
+    State(service): State<Arc<GameControlService>>,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    let Some(auth) = service.auth() else {
+        return Json(json!({
+            "success": false,
+            "error": "Authentication is disabled",
+        }))
+        .into_response();
+    };
+    let Some((token, principal, expires_at)) = auth.login(&request.username, &request.password) else {
+        println!("Failed login for user {}", request.username);
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "error": "Invalid username or password",
+            })),
+        )
+            .into_response();
+    };
+
+    println!("User {} logged in as {}", principal.name, principal.role.as_str());
+    // 启用 TLS 时只允许浏览器通过 HTTPS 发送会话 Cookie
+    let secure = if service.config().tls_cert_path.is_some() { "; Secure" } else { "" };
+    let cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Strict{}",
+        SESSION_COOKIE, token, SESSION_TTL_SECS, secure
+    );
+    (
+        [(header::SET_COOKIE, cookie)],
+        Json(json!({
+            "success": true,
+            "user": principal,
+            "expires_at": expires_at,
+        })),
+    )
+        .into_response()
+}
+
+async fn logout(State(service): State<Arc<GameControlService>>, headers: HeaderMap) -> impl IntoResponse {
+    if let (Some(auth), Some(token)) = (service.auth(), request_token(&headers)) {
+        auth.logout(&token);
+    }
+    let cookie = format!("{}=; Path=/; Max-Age=0; HttpOnly; SameSite=Strict", SESSION_COOKIE);
+    ([(header::SET_COOKIE, cookie)], Json(json!({ "success": true })))
+}
+
+async fn current_user(
+    State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    Json(json!({
+        "success": true,
+        "auth_enabled": service.auth().is_some(),
+        "user": principal,
+    }))
+}
+
+// Prometheus 抓取接口
+async fn prometheus_metrics(State(service): State<Arc<GameControlService>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        prometheus::render(&service).await,
+    )
+}
+
+// 事件订阅参数
+#[derive(Debug, Deserialize)]
+struct EventQuery {
+    // 逗号分隔的事件类型，例如 client_status,command_changed
+    types: Option<String>,
+    // 只推送该客户端的事件（以及紧急停止这类全局事件）
+    client_id: Option<String>,
+}
+
+// 以 Server-Sent Events 推送服务器事件，SSE 的事件名与 JSON 中的 type 字段相同
+// 订阅者处理太慢丢失事件时推送 lagged 事件，网页应重新加载完整状态
+async fn stream_events(
+    State(service): State<Arc<GameControlService>>,
+    Query(query): Query<EventQuery>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let filter = EventFilter {
+        kinds: query
+            .types
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(String::from)
+            .collect(),
+        client_id: query.client_id,
+    };
+    let receiver = service.subscribe_events();
+
+    let stream = futures::stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) if filter.matches(&event) => SseEvent::default()
+                    .event(event.kind())
+                    .data(serde_json::to_string(&event).unwrap_or_default()),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => SseEvent::default()
+                    .event("lagged")
+                    .data(json!({ "missed": missed }).to_string()),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (receiver, filter)));
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 async fn list_clients(
     State(service): State<Arc<GameControlService>>,
+    Query(query): Query<ClientListQuery>,
 ) -> impl IntoResponse {
+    let selector = match query.selector.as_deref().unwrap_or_default().parse::<Selector>() {
+        Ok(selector) => selector,
+        Err(e) => return Json(json!({
+            "success": false,
+            "error": e,
+        })),
+    };
     let clients = service.get_clients().await;
-    let client_list: Vec<_> = clients.iter().map(|(id, client)| {
+    let queue_lengths = service.queue_lengths().await;
+    let emergency_stop = service.emergency_stop().await;
+    let client_list: Vec<_> = clients.iter()
+        .filter(|(_, client)| query.state.is_none_or(|state| client.state == state))
+        .filter(|(_, client)| selector.matches(client))
+        .map(|(id, client)| {
         // 从客户端状态中提取当前命令信息
         let current_command = if let Some(metrics) = &client.status {
             if let (Some(cmd_id), Some(cmd), Some(started_at)) = (
@@ -82,30 +550,515 @@ async fn list_clients(
             name: client.name.clone(),
             client_type: client.client_type.clone(),
             version: client.version.clone(),
+            instance_key: client.instance_key.clone(),
+            labels: client.labels.clone(),
+            max_players: client.max_players,
+            max_idle_players: client.max_idle_players,
+            free_capacity: client.free_capacity(),
+            state: client.state,
+            capabilities: client.capabilities.clone(),
+            clock: client.clock.clone(),
             last_seen: client.last_seen,
             metrics: client.status.clone().unwrap_or_default(),
             current_command,
+            queue_length: queue_lengths.get(id).copied().unwrap_or(0),
         }
     }).collect();
 
     Json(json!({
         "success": true,
         "clients": client_list,
+        "emergency_stop": {
+            "active": emergency_stop.is_some(),
+            "reason": emergency_stop.as_ref().map(|stop| stop.reason.clone()),
+            "activated_at": emergency_stop.as_ref().map(|stop| stop.activated_at),
+        },
     }))
 }
 
+async fn remove_client(
+    State(service): State<Arc<GameControlService>>,
+    Path(client_id): Path<String>,
+) -> impl IntoResponse {
+    match service.remove_client(&client_id).await {
+        Ok(_) => Json(json!({
+            "success": true,
+            "message": "Client removed",
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        })),
+    }
+}
+
 async fn send_command(
     State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
     Json(request): Json<SendCommandRequest>,
 ) -> impl IntoResponse {
-    let command = PendingCommand {
+    let command = new_command(request.command, request.parameters, request.timeout_secs);
+    let client_id = match (request.client_id, request.selector) {
+        (Some(client_id), None) => client_id,
+        // 使用选择器时作为批量命令发送给所有匹配的客户端
+        (None, Some(selector)) => {
+            let targets = BatchTargets::new(Vec::new(), &selector);
+            return Json(create_batch(&service, targets, command, None, None, &principal.name).await);
+        }
+        _ => return Json(json!({
+            "success": false,
+            "error": "Specify exactly one of client_id or selector",
+        })),
+    };
+
+    let command_id = command.command_id.clone();
+    match service.add_command(&client_id, command, &principal.name).await {
+        Ok(CommandPlacement::Assigned) => Json(json!({
+            "success": true,
+            "message": "Command sent successfully",
+            "command_id": command_id,
+        })),
+        Ok(CommandPlacement::Queued(position)) => Json(json!({
+            "success": true,
+            "message": "Client is busy, command queued",
+            "command_id": command_id,
+            "queue_position": position,
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        })),
+    }
+}
+
+async fn send_batch(
+    State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<SendBatchRequest>,
+) -> impl IntoResponse {
+    let targets = BatchTargets::new(
+        request.client_ids.unwrap_or_default(),
+        request.selector.as_deref().unwrap_or_default(),
+    );
+    let command = new_command(request.command, request.parameters, request.timeout_secs);
+    let barrier = request.barrier.unwrap_or(false).then(|| {
+        Barrier::new(request.start_delay_ms.unwrap_or(0), request.barrier_timeout_secs.unwrap_or(0))
+    });
+    Json(create_batch(&service, targets, command, request.total_players, barrier, &principal.name).await)
+}
+
+async fn get_batch(
+    State(service): State<Arc<GameControlService>>,
+    Path(batch_id): Path<String>,
+) -> impl IntoResponse {
+    match service.get_batch(&batch_id).await {
+        Some((batch, statuses)) => Json(json!({
+            "success": true,
+            "batch": BatchInfo::new(batch_id, batch, statuses),
+        })),
+        None => Json(json!({
+            "success": false,
+            "error": "Batch not found",
+        })),
+    }
+}
+
+fn new_command(
+    command: String,
+    parameters: Option<std::collections::HashMap<String, String>>,
+    timeout_secs: Option<u32>,
+) -> PendingCommand {
+    PendingCommand {
         command_id: Uuid::new_v4().to_string(),
-        command: request.command,
-        parameters: request.parameters.unwrap_or_default(),
+        command,
+        parameters: parameters.unwrap_or_default(),
         created_at: Utc::now().timestamp(),
+        timeout_secs: timeout_secs.unwrap_or(0),
+        barrier: false,
+    }
+}
+
+async fn create_batch(
+    service: &GameControlService,
+    targets: Result<BatchTargets, String>,
+    command: PendingCommand,
+    total_players: Option<u32>,
+    barrier: Option<Barrier>,
+    issued_by: &str,
+) -> serde_json::Value {
+    let targets = match targets {
+        Ok(targets) => targets,
+        Err(e) => return json!({
+            "success": false,
+            "error": e,
+        }),
     };
+    match service.add_batch(targets, command, total_players, barrier, issued_by).await {
+        Ok((batch_id, batch)) => {
+            let accepted = batch.targets.iter().filter(|target| target.command_id.is_some()).count();
+            json!({
+                "success": accepted > 0,
+                "batch_id": batch_id,
+                "accepted": accepted,
+                "rejected": batch.targets.len() - accepted,
+                "targets": batch.targets,
+            })
+        }
+        Err(e) => json!({
+            "success": false,
+            "error": e.message(),
+        }),
+    }
+}
+
+async fn list_schedules(
+    State(service): State<Arc<GameControlService>>,
+) -> impl IntoResponse {
+    let mut schedules: Vec<_> = service
+        .get_schedules()
+        .await
+        .into_iter()
+        .map(|(id, schedule)| ScheduleInfo { id, schedule })
+        .collect();
+    schedules.sort_by_key(|info| info.schedule.created_at);
+    Json(json!({
+        "success": true,
+        "schedules": schedules,
+    }))
+}
+
+async fn create_schedule(
+    State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<ScheduleRequest>,
+) -> impl IntoResponse {
+    match request.into_schedule(&principal) {
+        Ok(schedule) => {
+            let id = service.add_schedule(schedule.clone()).await;
+            Json(json!({
+                "success": true,
+                "schedule": ScheduleInfo { id, schedule },
+            }))
+        }
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e,
+        })),
+    }
+}
+
+async fn get_schedule(
+    State(service): State<Arc<GameControlService>>,
+    Path(schedule_id): Path<String>,
+) -> impl IntoResponse {
+    match service.get_schedule(&schedule_id).await {
+        Some(schedule) => Json(json!({
+            "success": true,
+            "schedule": ScheduleInfo { id: schedule_id, schedule },
+        })),
+        None => Json(json!({
+            "success": false,
+            "error": "Schedule not found",
+        })),
+    }
+}
+
+async fn update_schedule(
+    State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
+    Path(schedule_id): Path<String>,
+    Json(request): Json<ScheduleRequest>,
+) -> impl IntoResponse {
+    let schedule = match request.into_schedule(&principal) {
+        Ok(schedule) => schedule,
+        Err(e) => return Json(json!({
+            "success": false,
+            "error": e,
+        })),
+    };
+    match service.update_schedule(&schedule_id, schedule).await {
+        Ok(schedule) => Json(json!({
+            "success": true,
+            "schedule": ScheduleInfo { id: schedule_id, schedule },
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.message(),
+        })),
+    }
+}
+
+async fn remove_schedule(
+    State(service): State<Arc<GameControlService>>,
+    Path(schedule_id): Path<String>,
+) -> impl IntoResponse {
+    match service.remove_schedule(&schedule_id).await {
+        Ok(_) => Json(json!({
+            "success": true,
+            "message": "Schedule removed",
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.message(),
+        })),
+    }
+}
+
+async fn list_workflows(
+    State(service): State<Arc<GameControlService>>,
+) -> impl IntoResponse {
+    let mut workflows: Vec<_> = service
+        .get_workflows()
+        .await
+        .into_iter()
+        .map(|(id, workflow)| WorkflowInfo { id, workflow })
+        .collect();
+    workflows.sort_by_key(|info| info.workflow.created_at);
+    Json(json!({
+        "success": true,
+        "workflows": workflows,
+    }))
+}
+
+// 请求体为 TOML 格式的工作流定义
+async fn start_workflow(
+    State(service): State<Arc<GameControlService>>,
+    Extension(principal): Extension<Principal>,
+    body: String,
+) -> impl IntoResponse {
+    let definition = match WorkflowDefinition::parse(&body) {
+        Ok(definition) => definition,
+        Err(e) => return Json(json!({
+            "success": false,
+            "error": format!("Invalid workflow: {}", e),
+        })),
+    };
+    let (id, workflow) = service.start_workflow(definition, &principal.name).await;
+    Json(json!({
+        "success": true,
+        "workflow": WorkflowInfo { id, workflow },
+    }))
+}
+
+async fn get_workflow(
+    State(service): State<Arc<GameControlService>>,
+    Path(workflow_id): Path<String>,
+) -> impl IntoResponse {
+    match service.get_workflow(&workflow_id).await {
+        Some(workflow) => Json(json!({
+            "success": true,
+            "workflow": WorkflowInfo { id: workflow_id, workflow },
+        })),
+        None => Json(json!({
+            "success": false,
+            "error": "Workflow not found",
+        })),
+    }
+}
+
+async fn cancel_workflow(
+    State(service): State<Arc<GameControlService>>,
+    Path(workflow_id): Path<String>,
+) -> impl IntoResponse {
+    match service.cancel_workflow(&workflow_id).await {
+        Ok(()) => Json(json!({
+            "success": true,
+            "message": "Workflow cancellation requested",
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.message(),
+        })),
+    }
+}
+
+async fn get_catalog(
+    State(service): State<Arc<GameControlService>>,
+    Query(query): Query<CatalogQuery>,
+) -> impl IntoResponse {
+    let Some(catalog) = service.catalog() else {
+        return Json(json!({
+            "success": true,
+            "enabled": false,
+            "commands": [],
+        }));
+    };
+
+    let commands: Vec<_> = catalog
+        .commands
+        .iter()
+        .filter(|command| query.client_type.as_deref().is_none_or(|client_type| command.supports(client_type)))
+        .collect();
+    Json(json!({
+        "success": true,
+        "enabled": true,
+        "commands": commands,
+    }))
+}
+
+async fn get_command(
+    State(service): State<Arc<GameControlService>>,
+    Path(command_id): Path<String>,
+) -> impl IntoResponse {
+    match service.get_command(&command_id).await {
+        Some(command) => Json(json!({
+            "success": true,
+            "command": CommandInfo::new(command_id, command),
+        })),
+        None => Json(json!({
+            "success": false,
+            "error": "Command not found",
+        })),
+    }
+}
+
+async fn cancel_command(
+    State(service): State<Arc<GameControlService>>,
+    Path(command_id): Path<String>,
+    Query(query): Query<CancelCommandQuery>,
+) -> impl IntoResponse {
+    let reason = query.reason.unwrap_or_default();
+    match service.request_cancel(&command_id, &reason).await {
+        Ok(CancelOutcome::Cancelled) => Json(json!({
+            "success": true,
+            "message": "Command cancelled",
+            "pending_confirmation": false,
+        })),
+        Ok(CancelOutcome::Requested) => Json(json!({
+            "success": true,
+            "message": "Cancellation sent to client",
+            "pending_confirmation": true,
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        })),
+    }
+}
+
+async fn engage_emergency_stop(
+    State(service): State<Arc<GameControlService>>,
+    request: Option<Json<EmergencyStopRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let cancelled = service
+        .engage_emergency_stop(request.reason.as_deref().unwrap_or_default())
+        .await;
+
+    Json(json!({
+        "success": true,
+        "message": "Emergency stop engaged",
+        "cancelled_commands": cancelled,
+    }))
+}
 
-    if let Err(e) = service.add_command(&request.client_id, command).await {
+async fn clear_emergency_stop(
+    State(service): State<Arc<GameControlService>>,
+) -> impl IntoResponse {
+    if let Err(e) = service.clear_emergency_stop().await {
+        return Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        }));
+    }
+
+    Json(json!({
+        "success": true,
+        "message": "Emergency stop cleared",
+    }))
+}
+
+// 指标历史查询参数，from 和 to 是 Unix时间戳（秒），默认查询最近一小时
+#[derive(Debug, Deserialize)]
+struct MetricQuery {
+    // 未指定时返回有历史数据的指标名称
+    name: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    // 聚合间隔（秒），未指定时自动选择
+    step: Option<i64>,
+}
+
+async fn get_client_metrics(
+    State(service): State<Arc<GameControlService>>,
+    Path(client_id): Path<String>,
+    Query(query): Query<MetricQuery>,
+) -> impl IntoResponse {
+    let Some(name) = query.name else {
+        return match service.metric_names(&client_id).await {
+            Ok(names) => Json(json!({
+                "success": true,
+                "names": names,
+            })),
+            Err(e) => Json(json!({
+                "success": false,
+                "error": e.message(),
+            })),
+        };
+    };
+
+    let to = query.to.unwrap_or_else(|| Utc::now().timestamp());
+    let Some(from) = query.from.or_else(|| to.checked_sub(3600)) else {
+        return Json(json!({
+            "success": false,
+            "error": "Invalid range",
+        }));
+    };
+    match service.metric_series(&client_id, &name, from, to, query.step.unwrap_or(0)).await {
+        Ok(series) => Json(json!({
+            "success": true,
+            "client_id": client_id,
+            "series": series,
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.message(),
+        })),
+    }
+}
+
+async fn list_queue(
+    State(service): State<Arc<GameControlService>>,
+    Path(client_id): Path<String>,
+) -> impl IntoResponse {
+    match service.queued_commands(&client_id).await {
+        Ok(queue) => {
+            let queue: Vec<QueuedCommand> = queue.into_iter().map(QueuedCommand::from).collect();
+            Json(json!({
+                "success": true,
+                "queue": queue,
+            }))
+        }
+        Err(e) => Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        })),
+    }
+}
+
+async fn reorder_queue(
+    State(service): State<Arc<GameControlService>>,
+    Path(client_id): Path<String>,
+    Json(request): Json<ReorderQueueRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = service.reorder_queue(&client_id, &request.command_ids).await {
+        return Json(json!({
+            "success": false,
+            "error": e.to_string(),
+        }));
+    }
+
+    Json(json!({
+        "success": true,
+        "message": "Queue reordered",
+    }))
+}
+
+async fn remove_queued(
+    State(service): State<Arc<GameControlService>>,
+    Path((client_id, command_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    if let Err(e) = service.remove_queued(&client_id, &command_id).await {
         return Json(json!({
             "success": false,
             "error": e.to_string(),
@@ -114,6 +1067,6 @@ async fn send_command(
 
     Json(json!({
         "success": true,
-        "message": "Command sent successfully",
+        "message": "Command removed from queue",
     }))
 }
@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::grpc::{BatchCounts, BatchTargets};
+use crate::selector::Selector;
+
+// 工作流定义，从 TOML 解析
+// 步骤组成有向无环图，依赖的步骤都结束后才会决定是否执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowDefinition {
+    pub name: String,
+    pub steps: Vec<StepDefinition>,
+}
+
+// 一个步骤要么向目标客户端发送命令并等待结束，要么只等待一段时间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDefinition {
+    pub id: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub when: StepCondition,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    // 命令步骤的目标，client_ids、selector 和 client_type 三选一
+    #[serde(default)]
+    pub client_ids: Vec<String>,
+    pub selector: Option<String>,
+    pub client_type: Option<String>,
+    // 按空闲容量分配给目标客户端的玩家总数
+    pub total_players: Option<u32>,
+    // 每条命令的执行超时时间（秒），0 表示使用服务器默认值
+    #[serde(default)]
+    pub command_timeout_secs: u32,
+    // 等待所有命令结束的最长时间（秒），超时后取消未结束的命令，步骤失败；0 表示一直等待
+    #[serde(default)]
+    pub timeout_secs: i64,
+    // 等待步骤的等待时间（秒）
+    pub wait_secs: Option<i64>,
+}
+
+// 步骤根据依赖步骤的结果决定是否执行，不执行的步骤标记为跳过
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepCondition {
+    // 所有依赖步骤都成功
+    #[default]
+    Success,
+    // 至少一个依赖步骤失败，用于失败后的清理或补救
+    Failure,
+    // 依赖步骤结束即可
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowState {
+    Running,
+    // 所有执行的步骤都成功
+    Succeeded,
+    // 至少一个步骤失败
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+    Cancelled,
+}
+
+impl StepState {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, StepState::Pending | StepState::Running)
+    }
+}
+
+// 一次工作流的执行进度
+#[derive(Debug, Clone, Serialize)]
+pub struct Workflow {
+    pub definition: WorkflowDefinition,
+    pub state: WorkflowState,
+    pub created_at: i64,
+    // 启动工作流的用户或令牌名称
+    pub created_by: String,
+    pub finished_at: Option<i64>,
+    // 与 definition.steps 一一对应
+    pub steps: Vec<StepProgress>,
+    // 操作员请求取消，由执行工作流的任务处理
+    #[serde(skip)]
+    pub cancel_requested: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepProgress {
+    pub id: String,
+    pub state: StepState,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+    // 命令步骤发送的批量命令
+    pub batch_id: Option<String>,
+    // 批量命令中各状态子命令的数量
+    pub counts: Option<BatchCounts>,
+    pub error: Option<String>,
+}
+
+// 依赖步骤都结束后对步骤的处理
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepDecision {
+    Run,
+    Skip,
+}
+
+impl WorkflowDefinition {
+    // 解析并检查工作流定义
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let definition: WorkflowDefinition = toml::from_str(content).map_err(|e| e.to_string())?;
+        definition.check()?;
+        Ok(definition)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("Workflow has no steps".to_string());
+        }
+        let mut ids = HashSet::new();
+        for step in &self.steps {
+            if !ids.insert(step.id.as_str()) {
+                return Err(format!("Duplicate step '{}'", step.id));
+            }
+        }
+        for step in &self.steps {
+            if let Some(dependency) = step.depends_on.iter().find(|id| !ids.contains(id.as_str())) {
+                return Err(format!("Step '{}' depends on unknown step '{}'", step.id, dependency));
+            }
+            match (&step.command, step.wait_secs) {
+                (Some(_), None) => {
+                    step.targets().map_err(|e| format!("Step '{}': {}", step.id, e))?;
+                }
+                (None, Some(wait_secs)) if wait_secs >= 0 => {}
+                (None, Some(_)) => return Err(format!("Step '{}': wait_secs must not be negative", step.id)),
+                _ => return Err(format!("Step '{}' must set exactly one of command or wait_secs", step.id)),
+            }
+        }
+
+        // 按依赖关系逐层移除步骤，无法移除的步骤构成环
+        let mut remaining: Vec<&StepDefinition> = self.steps.iter().collect();
+        let mut done = HashSet::new();
+        while !remaining.is_empty() {
+            let (ready, blocked): (Vec<_>, Vec<_>) = remaining
+                .into_iter()
+                .partition(|step| step.depends_on.iter().all(|id| done.contains(id.as_str())));
+            if ready.is_empty() {
+                let ids: Vec<_> = blocked.iter().map(|step| step.id.as_str()).collect();
+                return Err(format!("Steps form a dependency cycle: {}", ids.join(", ")));
+            }
+            done.extend(ready.iter().map(|step| step.id.as_str()));
+            remaining = blocked;
+        }
+        Ok(())
+    }
+}
+
+impl StepDefinition {
+    // 命令步骤的目标
+    pub fn targets(&self) -> Result<BatchTargets, String> {
+        match (&self.selector, &self.client_type) {
+            (None, Some(client_type)) if self.client_ids.is_empty() => {
+                Ok(BatchTargets::Selector(Selector::exact("type", client_type)))
+            }
+            (selector, None) => BatchTargets::new(self.client_ids.clone(), selector.as_deref().unwrap_or_default()),
+            _ => Err("Specify exactly one of client_ids, selector or client_type".to_string()),
+        }
+    }
+}
+
+impl Workflow {
+    pub fn new(definition: WorkflowDefinition, created_by: &str, now: i64) -> Self {
+        let steps = definition
+            .steps
+            .iter()
+            .map(|step| StepProgress {
+                id: step.id.clone(),
+                state: StepState::Pending,
+                started_at: None,
+                finished_at: None,
+                batch_id: None,
+                counts: None,
+                error: None,
+            })
+            .collect();
+        Self {
+            definition,
+            state: WorkflowState::Running,
+            created_at: now,
+            created_by: created_by.to_string(),
+            finished_at: None,
+            steps,
+            cancel_requested: false,
+        }
+    }
+
+    // 工作流发出的命令记录的发出者，附带启动工作流的用户
+    pub fn issuer(&self, workflow_id: &str) -> String {
+        format!("workflow:{} by {}", workflow_id, self.created_by)
+    }
+
+    fn step_state(&self, id: &str) -> Option<StepState> {
+        self.steps.iter().find(|step| step.id == id).map(|step| step.state)
+    }
+
+    // 依赖步骤都已结束、可以决定是否执行的下一个步骤
+    pub fn next_ready(&self) -> Option<(usize, StepDecision)> {
+        self.definition.steps.iter().enumerate().find_map(|(index, step)| {
+            if self.steps[index].state != StepState::Pending {
+                return None;
+            }
+            let states: Vec<StepState> = step
+                .depends_on
+                .iter()
+                .filter_map(|id| self.step_state(id))
+                .collect();
+            if !states.iter().all(|state| state.is_finished()) {
+                return None;
+            }
+            let run = match step.when {
+                StepCondition::Success => states.iter().all(|state| *state == StepState::Succeeded),
+                StepCondition::Failure => states.contains(&StepState::Failed),
+                StepCondition::Always => true,
+            };
+            Some((index, if run { StepDecision::Run } else { StepDecision::Skip }))
+        })
+    }
+
+    pub fn finish_step(&mut self, index: usize, state: StepState, error: Option<String>, now: i64) {
+        let step = &mut self.steps[index];
+        println!("Workflow '{}' step '{}': {:?}", self.definition.name, step.id, state);
+        step.state = state;
+        step.finished_at = Some(now);
+        step.error = error;
+    }
+
+    // 所有步骤结束后确定工作流的结果
+    pub fn update_state(&mut self, now: i64) {
+        if self.state != WorkflowState::Running || !self.steps.iter().all(|step| step.state.is_finished()) {
+            return;
+        }
+        self.state = if self.steps.iter().any(|step| step.state == StepState::Cancelled) {
+            WorkflowState::Cancelled
+        } else if self.steps.iter().any(|step| step.state == StepState::Failed) {
+            WorkflowState::Failed
+        } else {
+            WorkflowState::Succeeded
+        };
+        self.finished_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(steps: &str) -> Result<WorkflowDefinition, String> {
+        WorkflowDefinition::parse(&format!("name = \"test\"\n{}", steps))
+    }
+
+    #[test]
+    fn accepts_a_dag() {
+        let definition = parse(
+            r#"
+[[steps]]
+id = "start"
+command = "start_test"
+client_type = "load_test"
+
+[[steps]]
+id = "wait"
+depends_on = ["start"]
+wait_secs = 60
+
+[[steps]]
+id = "cleanup"
+depends_on = ["start", "wait"]
+when = "always"
+command = "stop_test"
+selector = "type=load_test"
+"#,
+        )
+        .unwrap();
+        assert_eq!(definition.steps.len(), 3);
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let error = parse(
+            r#"
+[[steps]]
+id = "wait"
+depends_on = ["start"]
+wait_secs = 60
+"#,
+        )
+        .unwrap_err();
+        assert!(error.contains("unknown step 'start'"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let error = parse(
+            r#"
+[[steps]]
+id = "first"
+wait_secs = 1
+
+[[steps]]
+id = "a"
+depends_on = ["first", "b"]
+wait_secs = 1
+
+[[steps]]
+id = "b"
+depends_on = ["a"]
+wait_secs = 1
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(error, "Steps form a dependency cycle: a, b");
+
+        let error = parse("[[steps]]\nid = \"a\"\ndepends_on = [\"a\"]\nwait_secs = 1\n").unwrap_err();
+        assert!(error.contains("cycle"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_steps() {
+        assert!(parse("steps = []").is_err());
+        assert!(parse("[[steps]]\nid = \"a\"\nwait_secs = 1\n[[steps]]\nid = \"a\"\nwait_secs = 1\n").is_err());
+        assert!(parse("[[steps]]\nid = \"a\"\n").is_err());
+        assert!(parse("[[steps]]\nid = \"a\"\nwait_secs = -1\n").is_err());
+        assert!(parse("[[steps]]\nid = \"a\"\ncommand = \"x\"\nwait_secs = 1\n").is_err());
+        assert!(parse("[[steps]]\nid = \"a\"\ncommand = \"x\"\nselector = \"type=a\"\nclient_type = \"a\"\n").is_err());
+    }
+}